@@ -0,0 +1,277 @@
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::f64;
+use results::Results;
+
+/// Significance level under which a difference between replica sets is flagged
+const SIGNIFICANCE_LEVEL: f64 = 0.05;
+
+/// Returns whether a metric missing from a results file means a zero value. This is the case for
+/// the distributions, which only list the ages that were seen.
+fn missing_is_zero(metric: &str) -> bool {
+    metric.starts_with("age_dist.") || metric.starts_with("drops_dist.") || metric.contains(".age_dist.")
+}
+
+/// Orders metric names segment by segment, numerically for numeric segments, so that
+/// "age_dist.10" comes after "age_dist.9"
+fn compare_metrics(a: &str, b: &str) -> Ordering {
+    let mut a_segments = a.split('.');
+    let mut b_segments = b.split('.');
+    loop {
+        match (a_segments.next(), b_segments.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a), Some(b)) => {
+                let ordering = match (a.parse::<u64>(), b.parse::<u64>()) {
+                    (Ok(a), Ok(b)) => a.cmp(&b),
+                    _ => a.cmp(b),
+                };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+        }
+    }
+}
+
+/// Mean and sample variance of a set of values
+fn mean_variance(values: &[f64]) -> (f64, f64) {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = if values.len() > 1 {
+        values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)
+    } else {
+        0.0
+    };
+    (mean, variance)
+}
+
+/// Difference between two means relative to the magnitude of the first one. None if the first
+/// one is zero.
+fn relative_difference(mean_a: f64, mean_b: f64) -> Option<f64> {
+    if mean_a != 0.0 {
+        Some((mean_b - mean_a) / mean_a.abs())
+    } else {
+        None
+    }
+}
+
+/// Natural logarithm of the gamma function (Lanczos approximation)
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+    let tmp = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+    let mut series = 1.000000000190015;
+    for (i, c) in COEFFICIENTS.iter().enumerate() {
+        series += c / (x + 1.0 + i as f64);
+    }
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+/// Continued fraction used by the regularized incomplete beta function
+fn beta_continued_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const MAX_ITERATIONS: usize = 200;
+    const EPSILON: f64 = 3.0e-14;
+    const TINY: f64 = 1.0e-300;
+    let (qab, qap, qam) = (a + b, a + 1.0, a - 1.0);
+    let mut c = 1.0;
+    let mut d = 1.0 - qab * x / qap;
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1.0 / d;
+    let mut h = d;
+    for m in 1..MAX_ITERATIONS + 1 {
+        let m = m as f64;
+        let m2 = 2.0 * m;
+        for &aa in &[
+            m * (b - m) * x / ((qam + m2) * (a + m2)),
+            -(a + m) * (qab + m) * x / ((a + m2) * (qap + m2)),
+        ] {
+            d = 1.0 + aa * d;
+            if d.abs() < TINY {
+                d = TINY;
+            }
+            c = 1.0 + aa / c;
+            if c.abs() < TINY {
+                c = TINY;
+            }
+            d = 1.0 / d;
+            h *= d * c;
+        }
+        if (d * c - 1.0).abs() < EPSILON {
+            break;
+        }
+    }
+    h
+}
+
+/// Regularized incomplete beta function I_x(a, b)
+fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front = (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_continued_fraction(a, b, x) / a
+    } else {
+        1.0 - front * beta_continued_fraction(b, a, 1.0 - x) / b
+    }
+}
+
+/// Two-sided p-value of Welch's t-test for the difference between the means of two samples.
+/// None if either sample has fewer than two values.
+fn welch_p_value(a: &[f64], b: &[f64]) -> Option<f64> {
+    if a.len() < 2 || b.len() < 2 {
+        return None;
+    }
+    let (mean_a, var_a) = mean_variance(a);
+    let (mean_b, var_b) = mean_variance(b);
+    let (se_a, se_b) = (var_a / a.len() as f64, var_b / b.len() as f64);
+    let se = se_a + se_b;
+    if se == 0.0 {
+        // No variability at all: any difference is significant
+        return Some(if mean_a == mean_b { 1.0 } else { 0.0 });
+    }
+    let t = (mean_a - mean_b) / se.sqrt();
+    let df = se.powi(2)
+        / (se_a.powi(2) / (a.len() as f64 - 1.0) + se_b.powi(2) / (b.len() as f64 - 1.0));
+    Some(incomplete_beta(df / 2.0, 0.5, df / (df + t * t)))
+}
+
+/// Returns the values of a metric in a set of results. Replicas missing the metric are skipped,
+/// unless a missing value means zero.
+fn values(results: &[Results], metric: &str) -> Vec<f64> {
+    results
+        .iter()
+        .filter_map(|r| match r.metrics().get(metric) {
+            Some(value) => Some(*value),
+            None if missing_is_zero(metric) => Some(0.0),
+            None => None,
+        })
+        .collect()
+}
+
+/// Formats a number for the comparison table
+fn format_value(value: Option<f64>) -> String {
+    match value {
+        None => "-".to_owned(),
+        Some(value) if value == value.trunc() && value.abs() < 1e15 => format!("{}", value),
+        Some(value) => format!("{:.3}", value),
+    }
+}
+
+/// Compares the results of two sets of replicas (or two single runs), metric by metric, and
+/// prints a markdown table with the means, their absolute and relative differences and, if both
+/// sets have at least two replicas, the p-value of Welch's t-test. Differences with a p-value
+/// below `SIGNIFICANCE_LEVEL` are flagged with a "*".
+pub fn compare(a: &[Results], b: &[Results]) -> String {
+    let mut metrics: Vec<&String> = a.iter()
+        .chain(b.iter())
+        .flat_map(|r| r.metrics().keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    metrics.sort_by(|x, y| compare_metrics(x, y));
+
+    let mut table = format!("Comparing {} replica(s) of A with {} replica(s) of B\n\n", a.len(), b.len());
+    table.push_str("| Metric                                   |        A |        B |  B - A   | (B - A) / A | p-value |   |\n");
+    table.push_str("|:-----------------------------------------|---------:|---------:|---------:|------------:|--------:|:-:|\n");
+    for metric in metrics {
+        let (values_a, values_b) = (values(a, metric), values(b, metric));
+        let mean = |values: &[f64]| if values.is_empty() { None } else { Some(mean_variance(values).0) };
+        let (mean_a, mean_b) = (mean(&values_a), mean(&values_b));
+        let diff = match (mean_a, mean_b) {
+            (Some(mean_a), Some(mean_b)) => Some(mean_b - mean_a),
+            _ => None,
+        };
+        let relative = match (mean_a, mean_b) {
+            (Some(mean_a), Some(mean_b)) => relative_difference(mean_a, mean_b),
+            _ => None,
+        };
+        let p_value = welch_p_value(&values_a, &values_b);
+        let flag = match p_value {
+            Some(p) if p < SIGNIFICANCE_LEVEL => "*",
+            _ => "",
+        };
+        table.push_str(&format!(
+            "| {:<40} | {:>8} | {:>8} | {:>8} | {:>11} | {:>7} | {:>1} |\n",
+            metric,
+            format_value(mean_a),
+            format_value(mean_b),
+            format_value(diff),
+            relative.map_or("-".to_owned(), |r| format!("{:.1}%", r * 100.0)),
+            p_value.map_or("-".to_owned(), |p| format!("{:.4}", p)),
+            flag
+        ));
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!((actual - expected).abs() <= tolerance, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn metric_order() {
+        let mut metrics = vec!["age_dist.10", "age_dist.9", "age", "output.adds", "age_dist.x"];
+        metrics.sort_by(|x, y| compare_metrics(x, y));
+        assert_eq!(metrics, vec!["age", "age_dist.9", "age_dist.10", "age_dist.x", "output.adds"]);
+    }
+
+    #[test]
+    fn relative_differences() {
+        assert_eq!(relative_difference(4.0, 5.0), Some(0.25));
+        assert_eq!(relative_difference(4.0, 3.0), Some(-0.25));
+        // relative to the magnitude, so that an increase is positive for negative means too
+        assert_eq!(relative_difference(-4.0, -3.0), Some(0.25));
+        assert_eq!(relative_difference(0.0, 3.0), None);
+    }
+
+    #[test]
+    fn incomplete_beta_and_gamma() {
+        assert_close(ln_gamma(5.0), 24f64.ln(), 1e-10);
+        assert_close(ln_gamma(0.5), f64::consts::PI.sqrt().ln(), 1e-10);
+        for &x in &[0.1, 0.25, 0.5, 0.9] {
+            // I_x(1, 1) = x, I_x(a, 1) = x^a and I_x(1/2, 1/2) = 2 asin(sqrt(x)) / pi
+            assert_close(incomplete_beta(1.0, 1.0, x), x, 1e-10);
+            assert_close(incomplete_beta(3.0, 1.0, x), x.powi(3), 1e-10);
+            assert_close(incomplete_beta(0.5, 0.5, x), 2.0 * x.sqrt().asin() / f64::consts::PI, 1e-10);
+        }
+        assert_eq!(incomplete_beta(2.0, 3.0, 0.0), 0.0);
+        assert_eq!(incomplete_beta(2.0, 3.0, 1.0), 1.0);
+        assert_close(incomplete_beta(4.0, 4.0, 0.5), 0.5, 1e-10);
+    }
+
+    #[test]
+    fn welch_t_test() {
+        // t = -2 with 8 degrees of freedom
+        let p = welch_p_value(&[1.0, 2.0, 3.0, 4.0, 5.0], &[3.0, 4.0, 5.0, 6.0, 7.0]).unwrap();
+        assert_close(p, 0.080516, 1e-6);
+        // with 2 degrees of freedom, p = 1 - |t| / sqrt(2 + t^2)
+        let p = welch_p_value(&[0.0, 2.0], &[4.0, 6.0]).unwrap();
+        assert_close(p, 1.0 - 0.8f64.sqrt(), 1e-9);
+        // the test is symmetric
+        assert_eq!(
+            welch_p_value(&[1.0, 2.0, 4.0], &[2.0, 5.0, 9.0, 11.0]),
+            welch_p_value(&[2.0, 5.0, 9.0, 11.0], &[1.0, 2.0, 4.0])
+        );
+        assert_eq!(welch_p_value(&[1.0, 1.0], &[1.0, 1.0]), Some(1.0));
+        assert_eq!(welch_p_value(&[1.0, 1.0], &[2.0, 2.0]), Some(0.0));
+        assert_eq!(welch_p_value(&[1.0], &[2.0, 3.0]), None);
+    }
+}
@@ -0,0 +1,209 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, Stdout, Write};
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+use termion;
+use termion::{clear, cursor};
+use termion::async_stdin;
+use termion::event::Key;
+use termion::input::{Keys, TermRead};
+use termion::raw::{IntoRawMode, RawTerminal};
+use termion::screen::AlternateScreen;
+use termion::AsyncReader;
+use network::Network;
+use network::observer::Observer;
+use network::prefix::Prefix;
+
+/// Minimum time between two redraws while running
+const REFRESH_INTERVAL_MS: u64 = 100;
+/// Time between two checks of the keyboard while paused
+const PAUSE_POLL_MS: u64 = 50;
+/// Number of merge and split lines kept in the activity log
+const ACTIVITY_LINES: usize = 8;
+/// Width of the longest bar of the age histogram
+const HISTOGRAM_WIDTH: usize = 50;
+
+/// The most recent merges and splits, recorded by observing the network
+#[derive(Default)]
+struct Activity {
+    lines: VecDeque<String>,
+    /// the current iteration
+    iteration: usize,
+}
+
+impl Activity {
+    fn log(&mut self, line: String) {
+        self.lines.push_back(line);
+        while self.lines.len() > ACTIVITY_LINES {
+            let _ = self.lines.pop_front();
+        }
+    }
+}
+
+impl Observer for Activity {
+    fn split_finished(&mut self, prefix: Prefix, children: (Prefix, Prefix)) {
+        let line = format!(
+            "{:>8}  split  {} -> {}, {}",
+            self.iteration,
+            prefix.label(),
+            children.0.label(),
+            children.1.label()
+        );
+        self.log(line);
+    }
+
+    fn merge_finished(&mut self, prefix: Prefix, sections: &[Prefix]) {
+        let merged: Vec<String> = sections.iter().map(Prefix::label).collect();
+        let line = format!("{:>8}  merge  {} -> {}", self.iteration, merged.join(", "), prefix.label());
+        self.log(line);
+    }
+
+    fn iteration_end(&mut self, iteration: usize, _network: &Network) {
+        self.iteration = iteration + 1;
+    }
+}
+
+/// A live terminal dashboard showing the state of the network while the simulation runs.
+/// It is drawn on the alternate screen, which is restored when the dashboard is dropped.
+/// Keys: space or 'p' pauses and resumes, 's' runs a single iteration while paused, 'q' quits.
+pub struct Dashboard {
+    screen: AlternateScreen<RawTerminal<Stdout>>,
+    keys: Keys<AsyncReader>,
+    paused: bool,
+    stepping: bool,
+    last_draw: Option<(Instant, usize)>,
+    rate: f64,
+    activity: Rc<RefCell<Activity>>,
+}
+
+impl Dashboard {
+    /// Takes over the terminal, and starts observing the splits and merges of `network`
+    pub fn new(network: &mut Network) -> Dashboard {
+        let stdout = io::stdout()
+            .into_raw_mode()
+            .expect("Couldn't switch the terminal to raw mode!");
+        let mut screen = AlternateScreen::from(stdout);
+        let _ = write!(screen, "{}", cursor::Hide);
+        let activity = Rc::new(RefCell::new(Activity::default()));
+        network.add_observer(Box::new(activity.clone()));
+        Dashboard {
+            screen,
+            keys: async_stdin().keys(),
+            paused: false,
+            stepping: false,
+            last_draw: None,
+            rate: 0.0,
+            activity,
+        }
+    }
+
+    /// Handles the keys pressed since the last call, redraws the dashboard if needed and blocks
+    /// while the simulation is paused. Returns false if the user asked to quit.
+    pub fn update(&mut self, iteration: usize, iterations: usize, network: &Network) -> bool {
+        if self.stepping {
+            // The requested single iteration has been run
+            self.stepping = false;
+            self.paused = true;
+            self.draw(iteration, iterations, network);
+        }
+        let due = match self.last_draw {
+            Some((instant, _)) => instant.elapsed() >= Duration::from_millis(REFRESH_INTERVAL_MS),
+            None => true,
+        };
+        if due {
+            self.draw(iteration, iterations, network);
+        }
+        loop {
+            while let Some(Ok(key)) = self.keys.next() {
+                match key {
+                    Key::Char('q') | Key::Ctrl('c') => return false,
+                    Key::Char(' ') | Key::Char('p') => {
+                        self.paused = !self.paused;
+                        self.draw(iteration, iterations, network);
+                    }
+                    Key::Char('s') if self.paused => {
+                        self.paused = false;
+                        self.stepping = true;
+                    }
+                    _ => (),
+                }
+            }
+            if !self.paused {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(PAUSE_POLL_MS));
+        }
+    }
+
+    fn draw(&mut self, iteration: usize, iterations: usize, network: &Network) {
+        if let Some((instant, last_iteration)) = self.last_draw {
+            let elapsed = instant.elapsed();
+            let seconds = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 * 1e-9;
+            if seconds > 0.0 && iteration > last_iteration {
+                self.rate = (iteration - last_iteration) as f64 / seconds;
+            }
+        }
+        self.last_draw = Some((Instant::now(), iteration));
+
+        let output = network.output();
+        let sections = network.num_sections();
+        let complete = network.complete_sections();
+        let state = if self.paused { "PAUSED" } else { "running" };
+        let mut lines = vec![
+            format!("Ageing simulation - {}", state),
+            String::new(),
+            format!("Iteration      {:>10} / {} ({:.0} it/s)", iteration, iterations, self.rate),
+            format!("Sections       {:>10}", sections),
+            format!(
+                "Complete       {:>10} ({:.0}%)",
+                complete,
+                complete as f64 / sections as f64 * 100.0
+            ),
+            format!("Section nodes  {:>10}", network.section_sizes().iter().sum::<usize>()),
+            format!(
+                "Rejection rate {:>9.0}% of sections, {:.0}% of adds",
+                network.rejection_rate() * 100.0,
+                output.rejections as f64 / output.adds.max(1) as f64 * 100.0
+            ),
+            String::new(),
+            "Age distribution:".to_owned(),
+        ];
+        lines.extend(histogram_lines(&network.age_distribution()));
+        lines.push(String::new());
+        lines.push("Recent merges and splits:".to_owned());
+        lines.extend(self.activity.borrow().lines.iter().cloned());
+        lines.push(String::new());
+        lines.push("[space/p] pause/resume  [s] step  [q] quit".to_owned());
+
+        let _ = write!(self.screen, "{}", clear::All);
+        for (row, line) in lines.iter().enumerate() {
+            let _ = write!(self.screen, "{}{}", cursor::Goto(1, row as u16 + 1), line);
+        }
+        let _ = self.screen.flush();
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        let _ = write!(self.screen, "{}", cursor::Show);
+        let _ = self.screen.flush();
+    }
+}
+
+/// Renders a distribution as horizontal bars scaled to `HISTOGRAM_WIDTH`
+fn histogram_lines(dist: &BTreeMap<u8, usize>) -> Vec<String> {
+    let max = dist.values().cloned().max().unwrap_or(0).max(1);
+    dist.iter()
+        .map(|(age, &count)| {
+            let width = (count * HISTOGRAM_WIDTH).div_ceil(max);
+            format!("{:>4} {:>7} {}", age, count, "#".repeat(width))
+        })
+        .collect()
+}
+
+/// Returns whether stdout is a terminal the dashboard can be drawn on
+pub fn is_available() -> bool {
+    termion::is_tty(&io::stdout())
+}
@@ -5,30 +5,36 @@ extern crate rand;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate termion;
 
+mod compare;
+mod dashboard;
 mod network;
 mod random;
 mod params;
+mod report;
+mod results;
+mod scenario;
 mod stats;
+mod stop;
 
-use random::random_range;
+use dashboard::Dashboard;
 use network::{Network, NetworkStructure};
-use params::Params;
+use network::flows::RelocationFlows;
+use network::history::Histories;
+use network::lineage::Lineage;
+use params::{Admission, AgeDist, NodeClass, Params, Phase};
+use std::cell::RefCell;
 use std::collections::BTreeMap;
-use clap::{App, Arg};
-
-/// Generates a random churn event in the network. There are three possible kinds:
-/// node joining, node leaving and node rejoining.
-fn random_event(network: &mut Network, probs: (u8, u8)) {
-    let x = random_range(0, 100);
-    if x < probs.0 {
-        network.add_random_node();
-    } else if x >= probs.0 && x < probs.0 + probs.1 {
-        network.drop_random_node();
-    } else {
-        network.rejoin_random_node();
-    }
-}
+use std::rc::Rc;
+use stats::Stats;
+use stop::StopChecker;
+use results::Results;
+use scenario::Scenario;
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+/// Number of buckets of the final section size histogram
+const SECTION_SIZE_BUCKETS: usize = 10;
 
 fn print_dist(mut dist: BTreeMap<u8, usize>) {
     let mut age = 1;
@@ -39,8 +45,34 @@ fn print_dist(mut dist: BTreeMap<u8, usize>) {
     }
 }
 
-fn get_params() -> Params {
-    let matches = App::new("Ageing Simulation")
+/// Prints the age distribution and the share of Elders of every node class
+fn print_classes(network: &Network) {
+    let classes = &network.params().node_classes;
+    let age_dists = network.class_age_distributions();
+    let elders = network.class_elders();
+    let total_nodes: usize = age_dists.iter().map(|dist| dist.values().sum::<usize>()).sum();
+    let total_elders: usize = elders.iter().sum();
+    println!("\n| Node class     |  Nodes   | Node share |  Elders  | Elder share |");
+    println!("|:---------------|---------:|-----------:|---------:|------------:|");
+    for (i, class) in classes.iter().enumerate() {
+        let nodes: usize = age_dists[i].values().sum();
+        println!(
+            "| {:<14} | {:>8} | {:>9.1}% | {:>8} | {:>10.1}% |",
+            class.name,
+            nodes,
+            nodes as f64 / total_nodes.max(1) as f64 * 100.0,
+            elders[i],
+            elders[i] as f64 / total_elders.max(1) as f64 * 100.0
+        );
+    }
+    for (class, dist) in classes.iter().zip(age_dists) {
+        println!("\nAge distribution of class {}:", class.name);
+        print_dist(dist);
+    }
+}
+
+fn get_matches() -> ArgMatches<'static> {
+    App::new("Ageing Simulation")
         .about("Simulates ageing in SAFE network")
         .arg(
             Arg::with_name("initage")
@@ -66,12 +98,20 @@ fn get_params() -> Params {
                 .help("Set the max number of young peers we allow in a section; 0 value means no control; default: 1")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("admission")
+                .long("admission")
+                .value_name("POLICY")
+                .help("Selects how complete sections admit young peers: max-young:MAX, ratio:INFANTS_PER_ADULT, target:SIZE, quota:YOUNG_PER_SECTION (network-wide) or probability:P; default: max-young with the --max_young value")
+                .takes_value(true)
+                .conflicts_with("max_young"),
+        )
         .arg(
             Arg::with_name("iterations")
                 .short("n")
                 .long("iterations")
                 .value_name("ITER")
-                .help("Number of iterations; default: 100000")
+                .help("Maximum number of iterations; default: 100000")
                 .takes_value(true),
         )
         .arg(
@@ -86,14 +126,122 @@ fn get_params() -> Params {
             Arg::with_name("p_add1")
                 .long("padd1")
                 .value_name("P")
-                .help("Probability that a peer will join during a step (0-100); default: 90")
+                .help("Probability that a peer will join during a step (0-100), when there is no --phase; default: 90")
                 .takes_value(true),
         )
         .arg(
             Arg::with_name("p_drop1")
                 .long("pdrop1")
                 .value_name("P")
-                .help("Probability that a peer will be dropped during a step (0-100); default: 7")
+                .help("Probability that a peer will be dropped during a step (0-100), when there is no --phase; default: 7")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("phase")
+                .long("phase")
+                .value_name("SPEC")
+                .help("Adds a phase to the churn schedule, like \"50000:90/7\" for 50000 iterations with add and drop probabilities of 90% and 7%, or \"20000:90/7->10/60\" for probabilities ramping linearly; can be repeated; the number of iterations defaults to the total of the phases")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .conflicts_with_all(&["p_add1", "p_drop1"]),
+        )
+        .arg(
+            Arg::with_name("bootstrap")
+                .long("bootstrap")
+                .value_name("NODES")
+                .help("Number of nodes the network starts with, already split into sections; default: 0")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("bootstrap_ages")
+                .long("bootstrap-ages")
+                .value_name("DIST")
+                .help("Ages of the bootstrap nodes as \"age:weight\" pairs, like \"5:4,6:2,7:1\"; default: weights halving from the initial age")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("snapshot_in")
+                .long("snapshot-in")
+                .value_name("FILE")
+                .help("Snapshot file describing the sections the network starts with")
+                .takes_value(true)
+                .conflicts_with_all(&["bootstrap", "bootstrap_ages"]),
+        )
+        .arg(
+            Arg::with_name("snapshot_out")
+                .long("snapshot-out")
+                .value_name("FILE")
+                .help("Output file for a snapshot of the final sections, which can be loaded with --snapshot-in")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("lineage_file")
+                .long("lineage")
+                .value_name("FILE")
+                .help("Records the genealogy of the sections and writes it to this CSV file")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("flap_window")
+                .long("flap-window")
+                .value_name("ITERATIONS")
+                .help("A section created by a merge that splits again within this number of iterations is counted as flapping by --lineage; default: 1000")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("histories_file")
+                .long("histories")
+                .value_name("FILE")
+                .help("Records the life histories of the nodes and writes them to this CSV file")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("flows_file")
+                .long("relocation-flows")
+                .value_name("FILE")
+                .help("Output CSV file for the relocations by source and target prefix lengths")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("warmup")
+                .long("warmup")
+                .value_name("ITER")
+                .help("Number of iterations excluded from the outputs; default: 0")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("mass_departure")
+                .long("mass-departure")
+                .value_name("SPEC")
+                .help("Removes a fraction of the nodes at once, like \"0.3,prefix=01,at=50000\" or \"0.1,ages=1-4,rate=0.0001\"; the optional target is prefix=BITS or ages=MIN-MAX; can be repeated")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("node_class")
+                .long("node-class")
+                .value_name("SPEC")
+                .help("Adds a class of nodes, like \"home,ratio=0.8,drop=exp,factor=3,rejoin=0.5,penalty=1\"; can be repeated; default: a single class behaving like the other options say")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("stop_at")
+                .long("stop-at")
+                .value_name("CONDITION")
+                .help("Ends the simulation early on nodes=COUNT, sections=COUNT, prefix-len=BITS, time=SECONDS or steady=WINDOW:TOLERANCE (mean age and section count within the relative tolerance over the last WINDOW iterations); can be repeated")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("retry")
+                .long("retry")
+                .value_name("SPEC")
+                .help("Makes rejected nodes try to join again, like \"delay=100,mode=same,attempts=5\" (the values are the defaults); mode=reroll gives them a new random name")
                 .takes_value(true),
         )
         .arg(
@@ -104,6 +252,27 @@ fn get_params() -> Params {
                 .help("Output file for network structure data")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("dot_file")
+                .long("dot-out")
+                .value_name("FILE")
+                .help("Output file for the final prefix tree in Graphviz DOT format")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("report_file")
+                .long("report")
+                .value_name("FILE")
+                .help("Output file for a self-contained HTML report of the simulation")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("stats_file")
+                .long("stats-out")
+                .value_name("FILE")
+                .help("Output file for the final section size stats per prefix length; JSON if the name ends with .json, CSV otherwise")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("drop_dist")
                 .long("drop-dist")
@@ -111,6 +280,13 @@ fn get_params() -> Params {
                 .help("Drop probability distribution based on the age: exponential(exp)/reverse-proportional(rev) (default: exponential)")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("elders")
+                .long("elders")
+                .value_name("POLICY")
+                .help("Selects how sections choose their elders among adults (oldest/hash/sticky/random); default: oldest")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("relocation_rate")
                 .short("r")
@@ -135,7 +311,54 @@ fn get_params() -> Params {
                 .help("Controls inhibition of relocations from small sections (-1, 0, 1, 2, ...); default: 1")
                 .takes_value(true),
         )
-        .get_matches();
+        .arg(
+            Arg::with_name("tui")
+                .long("tui")
+                .help("Shows a live dashboard instead of the periodic summaries (space: pause, s: step, q: quit)"),
+        )
+        .arg(
+            Arg::with_name("results_file")
+                .long("results-out")
+                .value_name("FILE")
+                .help("Output file for the final metrics, to be compared with the compare subcommand")
+                .takes_value(true),
+        )
+        .subcommand(
+            SubCommand::with_name("scenario")
+                .about("Runs a scenario file of scripted events against the network set up by the other options")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("Scenario file with commands like \"add 01 age=5\", \"drop <name>\", \"drop-oldest 01\", \"rejoin\", \"random 100\", \"expect sections=2\" and \"snapshot\"")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("compare")
+                .about("Compares the results files of two runs or two sets of replicas")
+                .arg(
+                    Arg::with_name("a")
+                        .short("a")
+                        .value_name("FILE")
+                        .help("Results file(s) of the first run or set of replicas")
+                        .takes_value(true)
+                        .multiple(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("b")
+                        .short("b")
+                        .value_name("FILE")
+                        .help("Results file(s) of the second run or set of replicas")
+                        .takes_value(true)
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
+        .get_matches()
+}
+
+fn get_params(matches: &ArgMatches) -> Params {
     let init_age = matches
         .value_of("initage")
         .unwrap_or("1")
@@ -158,9 +381,27 @@ fn get_params() -> Params {
         .unwrap_or("1")
         .parse()
         .expect("Max number of young peers must be a number!");
+    let admission = match matches.value_of("admission") {
+        Some(spec) => spec.parse().unwrap_or_else(|_| panic!("Invalid admission policy {:?}!", spec)),
+        None => Admission::MaxYoung(max_young),
+    };
+    let mut phases: Vec<Phase> = matches
+        .values_of("phase")
+        .into_iter()
+        .flatten()
+        .map(|spec| {
+            spec.parse()
+                .unwrap_or_else(|_| panic!("Invalid phase {:?}!", spec))
+        })
+        .collect();
+    let default_iterations = if phases.is_empty() {
+        "100000".to_owned()
+    } else {
+        phases.iter().map(|phase| phase.iterations).sum::<usize>().to_string()
+    };
     let iterations = matches
         .value_of("iterations")
-        .unwrap_or("100000")
+        .unwrap_or(&default_iterations)
         .parse()
         .expect("Number of iterations must be a number!");
     let summary_intervals = matches
@@ -168,6 +409,11 @@ fn get_params() -> Params {
         .unwrap_or("10000")
         .parse()
         .expect("Number of summary intervals must be a number!");
+    let elder_selection = matches
+        .value_of("elders")
+        .unwrap_or("oldest")
+        .parse()
+        .expect("Elder selection must be \"oldest\", \"hash\", \"sticky\" or \"random\"");
     let relocation_rate = matches
         .value_of("relocation_rate")
         .unwrap_or("standard")
@@ -188,13 +434,13 @@ fn get_params() -> Params {
         .parse()
         .expect("Relocation margin must be a number!");
     assert!(relocation_margin >= -1, "Relocation margin must be greater than or equal to -1!");
-    let p_add1 = matches
+    let p_add1: u8 = matches
         .value_of("p_add1")
         .unwrap_or("90")
         .parse()
         .expect("Add probability must be a number!");
     assert!(p_add1 < 100, "Probability must be between 0 and 100!");
-    let p_drop1 = matches
+    let p_drop1: u8 = matches
         .value_of("p_drop1")
         .unwrap_or("7")
         .parse()
@@ -204,15 +450,111 @@ fn get_params() -> Params {
         p_add1 + p_drop1 <= 100,
         "Add and drop probabilites must add up to at most 100!"
     );
+    if phases.is_empty() {
+        let probabilities = (p_add1 as f64, p_drop1 as f64);
+        phases.push(Phase {
+            iterations,
+            start: probabilities,
+            end: probabilities,
+        });
+    }
+    let bootstrap_nodes = matches
+        .value_of("bootstrap")
+        .unwrap_or("0")
+        .parse()
+        .expect("Number of bootstrap nodes must be a number!");
+    let bootstrap_ages = match matches.value_of("bootstrap_ages") {
+        Some(dist) => dist.parse()
+            .unwrap_or_else(|_| panic!("Bootstrap ages must be \"age:weight\" pairs separated by commas!")),
+        None => AgeDist::geometric(init_age),
+    };
+    let warmup = matches
+        .value_of("warmup")
+        .unwrap_or("0")
+        .parse()
+        .expect("Number of warm-up iterations must be a number!");
+    let mass_departures = matches
+        .values_of("mass_departure")
+        .into_iter()
+        .flatten()
+        .map(|spec| {
+            spec.parse()
+                .unwrap_or_else(|_| panic!("Invalid mass departure {:?}!", spec))
+        })
+        .collect();
+    let mut node_classes: Vec<NodeClass> = matches
+        .values_of("node_class")
+        .into_iter()
+        .flatten()
+        .map(|spec| {
+            spec.parse()
+                .unwrap_or_else(|_| panic!("Invalid node class {:?}!", spec))
+        })
+        .collect();
+    if node_classes.is_empty() {
+        node_classes.push(NodeClass::default());
+    }
+    assert!(node_classes.len() <= 256, "There can be at most 256 node classes!");
+    assert!(
+        node_classes.iter().any(|class| class.ratio > 0.0),
+        "At least one node class must have a positive ratio!"
+    );
+    let stop_conditions = matches
+        .values_of("stop_at")
+        .into_iter()
+        .flatten()
+        .map(|spec| {
+            spec.parse()
+                .unwrap_or_else(|_| panic!("Invalid stop condition {:?}!", spec))
+        })
+        .collect();
+    let retry = matches.value_of("retry").map(|spec| {
+        spec.parse()
+            .unwrap_or_else(|_| panic!("Invalid retry policy {:?}!", spec))
+    });
     let structure_output_file = matches.value_of("struct_file").map(|s| s.to_owned());
+    let dot_output_file = matches.value_of("dot_file").map(|s| s.to_owned());
+    let report_file = matches.value_of("report_file").map(|s| s.to_owned());
+    let stats_output_file = matches.value_of("stats_file").map(|s| s.to_owned());
+    let results_file = matches.value_of("results_file").map(|s| s.to_owned());
+    let snapshot_input_file = matches.value_of("snapshot_in").map(|s| s.to_owned());
+    let snapshot_output_file = matches.value_of("snapshot_out").map(|s| s.to_owned());
+    let lineage_output_file = matches.value_of("lineage_file").map(|s| s.to_owned());
+    let flap_window = matches
+        .value_of("flap_window")
+        .unwrap_or("1000")
+        .parse()
+        .expect("Flap window must be a number!");
+    let histories_output_file = matches.value_of("histories_file").map(|s| s.to_owned());
+    let flows_output_file = matches.value_of("flows_file").map(|s| s.to_owned());
+    let tui = matches.is_present("tui");
     Params {
         init_age,
         split_strategy: split,
-        max_young,
+        admission,
+        elder_selection,
         iterations,
         summary_intervals,
-        growth: (p_add1, p_drop1),
+        phases,
+        bootstrap_nodes,
+        bootstrap_ages,
+        warmup,
+        mass_departures,
+        node_classes,
+        stop_conditions,
+        retry,
         structure_output_file,
+        dot_output_file,
+        report_file,
+        stats_output_file,
+        results_file,
+        snapshot_input_file,
+        snapshot_output_file,
+        lineage_output_file,
+        flap_window,
+        histories_output_file,
+        flows_output_file,
+        tui,
         drop_dist,
         relocation_rate,
         distant_relocation_probability,
@@ -220,7 +562,7 @@ fn get_params() -> Params {
     }
 }
 
-fn output_structure_file(file: &str, data: &[NetworkStructure]) {
+fn output_structure_file(file: &str, start: usize, data: &[NetworkStructure]) {
     use std::fs::File;
     use std::io::Write;
     let mut file = File::create(file)
@@ -229,31 +571,240 @@ fn output_structure_file(file: &str, data: &[NetworkStructure]) {
     for (i, data) in data.into_iter().enumerate() {
         let _ = write!(
             file,
-            "{} {} {} {}\n",
-            i, data.size, data.sections, data.complete
+            "{} {} {} {} {} {} {} {} {}\n",
+            start + i,
+            data.size,
+            data.sections,
+            data.complete,
+            data.split_requests,
+            data.splits,
+            data.merge_requests,
+            data.merges_superseded,
+            data.merges
         );
     }
 }
 
+fn output_dot_file(file: &str, network: &Network) {
+    use std::fs::File;
+    use std::io::Write;
+    let mut file = File::create(file).unwrap_or_else(|_| panic!("Couldn't create file {}!", file));
+    let _ = file.write_all(network::dot::prefix_tree(network).as_bytes());
+}
+
+fn output_report_file(file: &str, network: &Network) {
+    use std::fs::File;
+    use std::io::Write;
+    let mut file = File::create(file).unwrap_or_else(|_| panic!("Couldn't create file {}!", file));
+    let _ = file.write_all(report::render(network).as_bytes());
+}
+
+fn output_stats_file(file: &str, network: &Network) {
+    use std::fs::File;
+    use std::io::Write;
+    let mut rows: Vec<(String, Stats<usize>)> = network
+        .section_sizes_by_prefix_len()
+        .iter()
+        .map(|(len, sizes)| (len.to_string(), Stats::new(sizes)))
+        .collect();
+    rows.push(("all".to_owned(), Stats::new(&network.section_sizes())));
+    let content = if file.ends_with(".json") {
+        let entries: Vec<String> = rows.iter()
+            .map(|(len, stats)| format!("\"{}\":{}", len, stats.to_json()))
+            .collect();
+        format!("{{{}}}\n", entries.join(","))
+    } else {
+        let mut lines = vec![format!("prefix_len,{}", Stats::<usize>::get_csv_header())];
+        lines.extend(rows.iter().map(|(len, stats)| format!("{},{}", len, stats.to_csv())));
+        lines.join("\n") + "\n"
+    };
+    let mut file = File::create(file).unwrap_or_else(|_| panic!("Couldn't create file {}!", file));
+    let _ = file.write_all(content.as_bytes());
+}
+
+fn load_snapshot_file(file: &str, params: &Params) -> Network {
+    use std::fs::File;
+    use std::io::Read;
+    let mut snapshot = String::new();
+    let _ = File::open(file)
+        .and_then(|mut f| f.read_to_string(&mut snapshot))
+        .unwrap_or_else(|e| panic!("Couldn't read file {}: {}", file, e));
+    network::snapshot::import(&snapshot, params.clone())
+        .unwrap_or_else(|e| panic!("Invalid snapshot {}: {}", file, e))
+}
+
+fn output_snapshot_file(file: &str, network: &Network) {
+    use std::fs::File;
+    use std::io::Write;
+    let mut file = File::create(file).unwrap_or_else(|_| panic!("Couldn't create file {}!", file));
+    let _ = file.write_all(network::snapshot::export(network).as_bytes());
+}
+
+fn output_lineage_file(file: &str, lineage: &Lineage) {
+    use std::fs::File;
+    use std::io::Write;
+    let mut file = File::create(file).unwrap_or_else(|_| panic!("Couldn't create file {}!", file));
+    let _ = file.write_all(lineage.to_csv().as_bytes());
+}
+
+fn output_histories_file(file: &str, histories: &Histories) {
+    use std::fs::File;
+    use std::io::Write;
+    let mut file = File::create(file).unwrap_or_else(|_| panic!("Couldn't create file {}!", file));
+    let _ = file.write_all(histories.to_csv().as_bytes());
+}
+
+fn output_flows_file(file: &str, flows: &RelocationFlows) {
+    use std::fs::File;
+    use std::io::Write;
+    let mut file = File::create(file).unwrap_or_else(|_| panic!("Couldn't create file {}!", file));
+    let _ = file.write_all(flows.to_csv().as_bytes());
+}
+
+fn run_scenario(file: &str, network: &mut Network) {
+    use std::fs::File;
+    use std::io::Read;
+    use std::process;
+    let mut text = String::new();
+    let _ = File::open(file)
+        .and_then(|mut f| f.read_to_string(&mut text))
+        .unwrap_or_else(|e| panic!("Couldn't read file {}: {}", file, e));
+    let scenario = Scenario::parse(&text).unwrap_or_else(|e| panic!("Invalid scenario {}: {}", file, e));
+    match scenario.run(network, 0) {
+        Ok(iterations) => {
+            println!("Network state:\n{}", network);
+            println!("Scenario {} passed ({} iterations)", file, iterations);
+        }
+        Err(e) => {
+            println!("Scenario {} failed at {}", file, e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Runs the compare subcommand: loads the results files and prints the comparison table
+fn run_compare(matches: &ArgMatches) {
+    let load = |name| -> Vec<Results> {
+        matches
+            .values_of(name)
+            .into_iter()
+            .flatten()
+            .map(|file| Results::read(file).unwrap_or_else(|e| panic!("Couldn't read results: {}", e)))
+            .collect()
+    };
+    print!("{}", compare::compare(&load("a"), &load("b")));
+}
+
 fn main() {
-    let params = get_params();
-    let mut network = Network::new(params.clone());
+    let matches = get_matches();
+    if let Some(matches) = matches.subcommand_matches("compare") {
+        run_compare(matches);
+        return;
+    }
+    let params = get_params(&matches);
+    if let Err(e) = params.validate() {
+        println!("Invalid parameters: {}", e);
+        std::process::exit(1);
+    }
+    let mut network = match params.snapshot_input_file {
+        Some(ref file) => load_snapshot_file(file, &params),
+        None => Network::new(params.clone()),
+    };
+    if params.bootstrap_nodes > 0 {
+        network.populate(params.bootstrap_nodes, &params.bootstrap_ages);
+    }
+    // The structure time series is only needed by the outputs plotting it
+    let capture_structure =
+        params.structure_output_file.is_some() || params.report_file.is_some();
+
+    if let Some(matches) = matches.subcommand_matches("scenario") {
+        run_scenario(matches.value_of("file").unwrap(), &mut network);
+        return;
+    }
+    // A scenario doesn't run the iterations the dashboard follows
+    let mut dashboard = if params.tui {
+        assert!(dashboard::is_available(), "The dashboard needs a terminal!");
+        Some(Dashboard::new(&mut network))
+    } else {
+        None
+    };
+
+    let lineage = params.lineage_output_file.clone().map(|file| {
+        let lineage = Rc::new(RefCell::new(Lineage::new(&network)));
+        network.add_observer(Box::new(lineage.clone()));
+        (file, lineage)
+    });
+    // The histories grow with the churn, so they are only recorded when asked for
+    let histories = params.histories_output_file.clone().map(|file| {
+        let histories = Rc::new(RefCell::new(Histories::new(&network)));
+        network.add_observer(Box::new(histories.clone()));
+        (file, histories)
+    });
+    let flows = Rc::new(RefCell::new(RelocationFlows::default()));
+    network.add_observer(Box::new(flows.clone()));
+
+    let mut iterations_run: usize = 0;
+    let mut stop_checker = StopChecker::new(&params.stop_conditions);
+    let mut stop_reason = None;
+    let mut current_phase = 0;
 
     for i in 0..params.iterations {
-        if i % params.summary_intervals == 0 {
+        if i == params.warmup && i > 0 {
+            network.start_measurement(i);
+        }
+        if let Some(ref mut dashboard) = dashboard {
+            if !dashboard.update(i, params.iterations, &network) {
+                stop_reason = Some("quit from the dashboard".to_owned());
+                break;
+            }
+        } else if i % params.summary_intervals == 0 {
             println!("Iteration {}...", i);
             println!("Network state:\n{}", network);
             println!("");            
         }
+        for departure in &params.mass_departures {
+            if departure.is_due(i) {
+                network.mass_departure(i, departure);
+            }
+        }
+        let (phase, probabilities) = params.churn_probabilities(i);
+        if phase != current_phase {
+            current_phase = phase;
+            if dashboard.is_none() {
+                println!("Iteration {}: phase {} starts\n", i, phase + 1);
+            }
+        }
         // Generate a random event...
-        random_event(&mut network, params.growth);
+        network.random_event(probabilities);
         // ... and process the churn cascade that may happen
         // (every churn event may trigger other churn events, that
         // may trigger others etc.)
         network.process_events();
+        network.end_iteration(i);
+        if capture_structure {
+            network.capture_network_structure();
+        }
+        iterations_run += 1;
+        stop_reason = stop_checker.check(i, &network);
+        if stop_reason.is_some() {
+            break;
+        }
     }
 
-    println!("...Iteration {}", params.iterations - 1);
+    if dashboard.is_some() {
+        // Restore the terminal before printing the final state
+        drop(dashboard);
+        println!("Seed: {:?}", random::seed());
+    }
+
+    println!("...Iteration {}", iterations_run.saturating_sub(1));
+    println!(
+        "Stopped: {}",
+        stop_reason.unwrap_or_else(|| format!("all {} iterations run", params.iterations))
+    );
+    if params.warmup > 0 {
+        println!("Measurement window started at iteration {}", params.warmup);
+    }
     println!("Network state:\n{}", network);
     println!("");
 
@@ -263,12 +814,58 @@ fn main() {
     println!("\nAge distribution:");
     print_dist(age_dist);
 
-    network.capture_network_structure();
+    if params.node_classes.len() > 1 {
+        print_classes(&network);
+    }
+
     let drop_dist = &network.output().drops_dist;
     println!("\nDrops distribution by age:");
     print_dist(drop_dist.clone());
 
+    println!("\nSection size distribution:");
+    print!("{}", Stats::new(&network.section_sizes()).histogram(SECTION_SIZE_BUCKETS));
+
+    if let Some((_, ref lineage)) = lineage {
+        print!("\nSection lineage:\n{}", lineage.borrow());
+    }
+    if let Some((_, ref histories)) = histories {
+        print!("\nNode histories:\n{}", histories.borrow());
+    }
+    print!("\nRelocation flows by prefix length:\n{}", flows.borrow());
+
     if let Some(ref file) = params.structure_output_file {
-        output_structure_file(file, &network.output().network_structure);
+        output_structure_file(file, network.output().measurement_start, &network.output().network_structure);
+    }
+
+    if let Some(ref file) = params.dot_output_file {
+        output_dot_file(file, &network);
+    }
+
+    if let Some(ref file) = params.report_file {
+        output_report_file(file, &network);
+    }
+
+    if let Some(ref file) = params.stats_output_file {
+        output_stats_file(file, &network);
+    }
+
+    if let Some(ref file) = params.snapshot_output_file {
+        output_snapshot_file(file, &network);
+    }
+
+    if let Some((ref file, ref lineage)) = lineage {
+        output_lineage_file(file, &lineage.borrow());
+    }
+
+    if let Some((ref file, ref histories)) = histories {
+        output_histories_file(file, &histories.borrow());
+    }
+
+    if let Some(ref file) = params.flows_output_file {
+        output_flows_file(file, &flows.borrow());
+    }
+
+    if let Some(ref file) = params.results_file {
+        Results::from_network(&network).write(file);
     }
 }
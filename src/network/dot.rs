@@ -0,0 +1,82 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use network::Network;
+use network::prefix::Prefix;
+use network::section::Section;
+
+/// Fill colours of the leaves, by decreasing priority of the state they represent
+const MERGING_COLOUR: &str = "orange";
+const SPLITTING_COLOUR: &str = "lightblue";
+const REJECTING_COLOUR: &str = "pink";
+const INCOMPLETE_COLOUR: &str = "red";
+const COMPLETE_COLOUR: &str = "palegreen";
+
+/// Returns the identifier of the graph node representing a prefix
+fn node_id(prefix: &Prefix) -> String {
+    format!("\"p{}\"", prefix)
+}
+
+/// Returns the fill colour of a leaf according to the state of its section
+fn leaf_colour(network: &Network, section: &Section) -> &'static str {
+    if section.is_merging() {
+        MERGING_COLOUR
+    } else if section.is_splitting() {
+        SPLITTING_COLOUR
+    } else if section.reject_young_node(network.params(), network.young_per_section()) {
+        REJECTING_COLOUR
+    } else if !section.is_complete() {
+        INCOMPLETE_COLOUR
+    } else {
+        COMPLETE_COLOUR
+    }
+}
+
+/// Renders the prefix tree of the network in the Graphviz DOT language.
+/// The leaves are the sections, labelled with their node counts and coloured by state:
+/// merging, splitting, rejecting young nodes, incomplete or complete. The inner nodes are the
+/// ancestors of the sections, so a lopsided tree shows up as branches of different depths.
+pub fn prefix_tree(network: &Network) -> String {
+    let sections = network.sections();
+    let mut inner = BTreeSet::new();
+    for prefix in sections.keys() {
+        let mut ancestor = *prefix;
+        while ancestor.len() > 0 {
+            ancestor = ancestor.shorten();
+            inner.insert(ancestor);
+        }
+    }
+
+    let mut dot = String::new();
+    let _ = writeln!(dot, "digraph prefix_tree {{");
+    let _ = writeln!(dot, "    node [fontname=\"monospace\"];");
+    for prefix in &inner {
+        let _ = writeln!(
+            dot,
+            "    {} [label=\"{}\", shape=ellipse];",
+            node_id(prefix),
+            prefix.label()
+        );
+    }
+    for (prefix, section) in sections {
+        let _ = writeln!(
+            dot,
+            "    {} [label=\"{}\\n{} nodes\\nE {} / A {} / I {}\\n{}\", shape=box, style=filled, \
+             fillcolor={}];",
+            node_id(prefix),
+            prefix.label(),
+            section.len(),
+            section.num_elders(),
+            section.num_adults(),
+            section.num_infants(),
+            if section.is_complete() { "complete" } else { "incomplete" },
+            leaf_colour(network, section)
+        );
+    }
+    for prefix in inner.iter().chain(sections.keys()) {
+        if prefix.len() > 0 {
+            let _ = writeln!(dot, "    {} -> {};", node_id(&prefix.shorten()), node_id(prefix));
+        }
+    }
+    let _ = writeln!(dot, "}}");
+    dot
+}
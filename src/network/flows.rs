@@ -0,0 +1,87 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use network::node::Node;
+use network::observer::Observer;
+use network::prefix::Prefix;
+use stats::{Accumulator, Stats};
+
+/// Number of bits of a name
+const NAME_BITS: usize = 256;
+
+/// Aggregates the relocations by the prefix lengths of their source and target sections
+#[derive(Clone, Default)]
+pub struct RelocationFlows {
+    /// the number of relocations, and of relocations to the section of the relocation name
+    /// itself rather than to one of its neighbours, for each pair of source and target prefix
+    /// lengths
+    matrix: BTreeMap<(u8, u8), (u64, u64)>,
+    /// the base-2 logarithm of the XOR distance between the source and target prefixes, rounded
+    /// up: 256 minus the number of leading bits they share, or 0 if they are the same. Unlike
+    /// `Output::relocation_distance`, which counts the differing bits, it measures how far apart
+    /// the sections are in the namespace.
+    log_distance: Accumulator,
+}
+
+impl RelocationFlows {
+    /// Returns the number of relocations to the section of the relocation name itself
+    pub fn same_section(&self) -> u64 {
+        self.matrix.values().map(|&(_, same)| same).sum()
+    }
+
+    /// Returns the matrix as CSV, one line per pair of source and target prefix lengths
+    pub fn to_csv(&self) -> String {
+        let mut lines = vec!["source_len,target_len,relocations,same_section".to_owned()];
+        lines.extend(self.matrix.iter().map(|(&(source, target), &(relocations, same))| {
+            format!("{},{},{},{}", source, target, relocations, same)
+        }));
+        lines.join("\n") + "\n"
+    }
+}
+
+impl Observer for RelocationFlows {
+    fn node_relocated(&mut self, from: Prefix, via: Prefix, to: Prefix, _old: &Node, _new: &Node) {
+        let cell = self.matrix.entry((from.len(), to.len())).or_default();
+        cell.0 += 1;
+        if to == via {
+            cell.1 += 1;
+        }
+        self.log_distance.add(NAME_BITS - from.xor_distance(&to).leading_zeros());
+    }
+
+    fn measurement_started(&mut self, _iteration: usize) {
+        *self = Default::default();
+    }
+}
+
+// Display the matrix as a markdown table, with a column per target prefix length
+impl fmt::Display for RelocationFlows {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let sources: BTreeSet<u8> = self.matrix.keys().map(|&(source, _)| source).collect();
+        let targets: BTreeSet<u8> = self.matrix.keys().map(|&(_, target)| target).collect();
+        write!(fmt, "| Source \\ Target |")?;
+        for target in &targets {
+            write!(fmt, " {:>6} |", target)?;
+        }
+        writeln!(fmt)?;
+        write!(fmt, "|----------------:|")?;
+        for _ in &targets {
+            write!(fmt, "-------:|")?;
+        }
+        writeln!(fmt)?;
+        for source in &sources {
+            write!(fmt, "| {:>15} |", source)?;
+            for target in &targets {
+                let count = self.matrix.get(&(*source, *target)).map_or(0, |&(count, _)| count);
+                write!(fmt, " {:>6} |", count)?;
+            }
+            writeln!(fmt)?;
+        }
+        writeln!(fmt)?;
+        writeln!(fmt, "| Same section   | {:>8} |", self.same_section())?;
+        writeln!(fmt)?;
+        writeln!(fmt, "| Relocation      {}", Stats::<usize>::get_header_line())?;
+        writeln!(fmt, "|:----------------{}", Stats::<usize>::get_separator_line())?;
+        writeln!(fmt, "| Log2 XOR dist.  | {}", self.log_distance)?;
+        Ok(())
+    }
+}
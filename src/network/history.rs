@@ -0,0 +1,283 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use network::Network;
+use network::node::Node;
+use network::observer::Observer;
+use network::prefix::{Name, Prefix};
+use stats::Stats;
+
+/// A relocation of a node
+#[derive(Clone, Copy, Debug)]
+pub struct Relocation {
+    pub iteration: usize,
+    pub from: Prefix,
+    pub to: Prefix,
+    /// the age of the node after the relocation
+    pub age: u8,
+}
+
+/// The life of one node, followed through its relocations, drops and rejoins
+#[derive(Clone, Debug, Default)]
+pub struct NodeHistory {
+    /// the iteration during which the node joined; None if it was there when the recording
+    /// started
+    pub joined: Option<usize>,
+    /// the iteration during which the node first became an Adult
+    pub adult: Option<usize>,
+    /// the iteration during which the node first became an Elder
+    pub elder: Option<usize>,
+    /// the number of iterations spent as an Elder, not counting the current period
+    pub elder_time: usize,
+    /// the iteration the current period as an Elder started, with the number of sections the
+    /// node is an Elder of (it can be two while a split is processed)
+    pub elder_since: Option<(usize, usize)>,
+    pub relocations: Vec<Relocation>,
+    /// the iterations during which the node left the network
+    pub drops: Vec<usize>,
+    /// the iterations during which the node rejoined the network
+    pub rejoins: Vec<usize>,
+}
+
+impl NodeHistory {
+    /// Returns the number of iterations spent as an Elder up to iteration `iteration`
+    pub fn elder_time_at(&self, iteration: usize) -> usize {
+        self.elder_time + self.elder_since.map_or(0, |(since, _)| iteration - since)
+    }
+}
+
+/// Records the life of every node by observing the network
+pub struct Histories {
+    /// the histories indexed by node id
+    histories: BTreeMap<u64, NodeHistory>,
+    /// the id of the node with each name
+    ids: BTreeMap<Name, u64>,
+    /// the Elders of each section
+    elders: BTreeMap<Prefix, BTreeSet<Name>>,
+    /// the current iteration
+    iteration: usize,
+}
+
+impl Histories {
+    /// Starts recording with the nodes currently in the sections of the network
+    pub fn new(network: &Network) -> Histories {
+        let mut histories = Histories {
+            histories: BTreeMap::new(),
+            ids: BTreeMap::new(),
+            elders: BTreeMap::new(),
+            iteration: 0,
+        };
+        for section in network.sections().values() {
+            for node in section.nodes() {
+                histories.register(&node);
+            }
+            let elders = BTreeSet::new();
+            histories.elders_changed(section.prefix(), &elders, section.elder_names());
+        }
+        histories
+    }
+
+    /// Records that the node named `name` became an Elder of one more section
+    fn add_seat(&mut self, name: &Name) {
+        let iteration = self.iteration;
+        let id = self.ids.get(name).cloned();
+        if let Some(history) = id.and_then(|id| self.histories.get_mut(&id)) {
+            history.elder = history.elder.or(Some(iteration));
+            history.elder_since = match history.elder_since {
+                Some((since, seats)) => Some((since, seats + 1)),
+                None => Some((iteration, 1)),
+            };
+        }
+    }
+
+    /// Records that the node named `name` stopped being an Elder of one section
+    fn remove_seat(&mut self, name: &Name) {
+        let iteration = self.iteration;
+        let id = self.ids.get(name).cloned();
+        if let Some(history) = id.and_then(|id| self.histories.get_mut(&id)) {
+            history.elder_since = match history.elder_since {
+                Some((since, seats)) if seats > 1 => Some((since, seats - 1)),
+                Some((since, _)) => {
+                    history.elder_time += iteration - since;
+                    None
+                }
+                None => None,
+            };
+        }
+    }
+
+    /// Records the name of a node, and returns its history
+    fn register(&mut self, node: &Node) -> &mut NodeHistory {
+        let _ = self.ids.insert(node.name(), node.id());
+        self.histories.entry(node.id()).or_default()
+    }
+
+    /// Returns the number of iterations between joining and the first time as an Adult, of the
+    /// nodes that joined and became Adults
+    pub fn times_to_adulthood(&self) -> Vec<usize> {
+        self.histories
+            .values()
+            .filter_map(|history| match (history.joined, history.adult) {
+                (Some(joined), Some(adult)) => Some(adult - joined),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the number of iterations between joining and the first time as an Elder, of the
+    /// nodes that joined and became Elders
+    pub fn times_to_elder(&self) -> Vec<usize> {
+        self.histories
+            .values()
+            .filter_map(|history| match (history.joined, history.elder) {
+                (Some(joined), Some(elder)) => Some(elder - joined),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the histories as CSV, one line per node. Relocations are written as
+    /// "<iteration>:<source prefix>-><target prefix>:<age>", drops and rejoins as iterations,
+    /// separated by spaces.
+    pub fn to_csv(&self) -> String {
+        let optional = |value: Option<usize>| value.map_or(String::new(), |v| v.to_string());
+        let iterations = |values: &[usize]| {
+            values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" ")
+        };
+        let mut lines = vec!["id,joined,adult,elder,elder_time,relocations,drops,rejoins".to_owned()];
+        lines.extend(self.histories.iter().map(|(id, history)| {
+            let relocations: Vec<String> = history
+                .relocations
+                .iter()
+                .map(|r| format!("{}:{}->{}:{}", r.iteration, r.from.label(), r.to.label(), r.age))
+                .collect();
+            format!(
+                "{},{},{},{},{},{},{},{}",
+                id,
+                optional(history.joined),
+                optional(history.adult),
+                optional(history.elder),
+                history.elder_time_at(self.iteration),
+                relocations.join(" "),
+                iterations(&history.drops),
+                iterations(&history.rejoins)
+            )
+        }));
+        lines.join("\n") + "\n"
+    }
+}
+
+impl Observer for Histories {
+    fn node_joined(&mut self, node: &Node, _attempts: usize, _waited: usize) {
+        let iteration = self.iteration;
+        let history = self.register(node);
+        history.joined = Some(iteration);
+        if node.is_adult() {
+            history.adult = Some(iteration);
+        }
+    }
+
+    fn node_dropped(&mut self, dropped: Option<(Prefix, &Node)>) {
+        if let Some((_, node)) = dropped {
+            let iteration = self.iteration;
+            self.register(node).drops.push(iteration);
+        }
+    }
+
+    fn node_rejoined(&mut self, node: Option<&Node>) {
+        if let Some(node) = node {
+            let iteration = self.iteration;
+            self.register(node).rejoins.push(iteration);
+        }
+    }
+
+    fn node_relocated(&mut self, from: Prefix, _via: Prefix, to: Prefix, _old: &Node, new: &Node) {
+        let iteration = self.iteration;
+        let history = self.register(new);
+        history.relocations.push(Relocation {
+            iteration,
+            from,
+            to,
+            age: new.age(),
+        });
+        if new.is_adult() && history.adult.is_none() {
+            history.adult = Some(iteration);
+        }
+    }
+
+    fn elders_changed(&mut self, prefix: Prefix, _old: &BTreeSet<Name>, new: &BTreeSet<Name>) {
+        // The change is applied to the recorded Elders, which also follow splits and merges
+        let old = self.elders.insert(prefix, new.clone()).unwrap_or_default();
+        for name in new.difference(&old) {
+            self.add_seat(name);
+        }
+        for name in old.difference(new) {
+            self.remove_seat(name);
+        }
+    }
+
+    fn split_finished(&mut self, prefix: Prefix, children: (Prefix, Prefix)) {
+        // Both halves start with the Elders of the section, until they process their departures
+        let elders = self.elders.remove(&prefix).unwrap_or_default();
+        for name in &elders {
+            self.add_seat(name);
+        }
+        let _ = self.elders.insert(children.0, elders.clone());
+        let _ = self.elders.insert(children.1, elders);
+    }
+
+    fn merge_finished(&mut self, prefix: Prefix, sections: &[Prefix]) {
+        // The merged section starts with the Elders of all the sections
+        let mut merged = BTreeSet::new();
+        for section in sections {
+            for name in self.elders.remove(section).unwrap_or_default() {
+                if !merged.insert(name) {
+                    self.remove_seat(&name);
+                }
+            }
+        }
+        let _ = self.elders.insert(prefix, merged);
+    }
+
+    fn mass_departure(&mut self, iteration: usize, departed: &[(Prefix, Node)], _network: &Network) {
+        for (_, node) in departed {
+            self.register(node).drops.push(iteration);
+        }
+    }
+
+    fn iteration_end(&mut self, iteration: usize, _network: &Network) {
+        self.iteration = iteration + 1;
+    }
+
+    fn measurement_started(&mut self, iteration: usize) {
+        // The histories start over, with the current Elders starting a new period as Elders
+        self.iteration = iteration;
+        for history in self.histories.values_mut() {
+            *history = NodeHistory {
+                elder: history.elder_since.map(|_| iteration),
+                elder_since: history.elder_since.map(|(_, seats)| (iteration, seats)),
+                ..Default::default()
+            };
+        }
+    }
+}
+
+// Display distributions over the node histories as a markdown table
+impl fmt::Display for Histories {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let relocations: Vec<usize> = self.histories.values().map(|h| h.relocations.len()).collect();
+        let elder_times: Vec<usize> = self.histories
+            .values()
+            .filter(|h| h.elder.is_some())
+            .map(|h| h.elder_time_at(self.iteration))
+            .collect();
+        let drops: Vec<usize> = self.histories.values().map(|h| h.drops.len()).collect();
+        writeln!(fmt, "| Node history     {}", Stats::<usize>::get_header_line())?;
+        writeln!(fmt, "|:-----------------{}", Stats::<usize>::get_separator_line())?;
+        writeln!(fmt, "| Time to adult    | {}", Stats::new(&self.times_to_adulthood()))?;
+        writeln!(fmt, "| Time to elder    | {}", Stats::new(&self.times_to_elder()))?;
+        writeln!(fmt, "| Relocations      | {}", Stats::new(&relocations))?;
+        writeln!(fmt, "| Time as elder    | {}", Stats::new(&elder_times))?;
+        writeln!(fmt, "| Drops            | {}", Stats::new(&drops))?;
+        Ok(())
+    }
+}
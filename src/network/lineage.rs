@@ -0,0 +1,264 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::mem;
+use network::Network;
+use network::observer::Observer;
+use network::prefix::Prefix;
+use stats::Stats;
+
+/// What created or destroyed a section
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cause {
+    /// the section was there when the recording started
+    Start,
+    /// the section was split into two, or is one half of a split section
+    Split,
+    /// the section was merged with others, or results from merging others
+    Merge,
+}
+
+impl fmt::Display for Cause {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Cause::Start => "start",
+            Cause::Split => "split",
+            Cause::Merge => "merge",
+        };
+        write!(fmt, "{}", name)
+    }
+}
+
+/// The history of one instance of a prefix, from its creation to its destruction
+#[derive(Clone, Debug)]
+pub struct SectionRecord {
+    pub prefix: Prefix,
+    /// the iteration during which the section was created
+    pub created: usize,
+    pub origin: Cause,
+    /// the indices of the records of the sections it was created from
+    pub parents: Vec<usize>,
+    /// the indices of the records of the sections it was replaced by
+    pub children: Vec<usize>,
+    /// the iteration during which the section was destroyed, and by what; None if it still exists
+    pub destroyed: Option<(usize, Cause)>,
+}
+
+impl SectionRecord {
+    /// Returns the number of iterations the section existed for, if it was destroyed
+    pub fn lifetime(&self) -> Option<usize> {
+        self.destroyed.map(|(destroyed, _)| destroyed - self.created)
+    }
+
+    /// Returns whether the section was created by a merge and split again within `window`
+    /// iterations
+    pub fn is_flapping(&self, window: usize) -> bool {
+        self.origin == Cause::Merge
+            && match self.destroyed {
+                Some((destroyed, Cause::Split)) => destroyed - self.created <= window,
+                _ => false,
+            }
+    }
+}
+
+/// Records the genealogy of the sections by observing the splits and merges of the network
+pub struct Lineage {
+    records: Vec<SectionRecord>,
+    /// the index of the record of each existing section
+    living: BTreeMap<Prefix, usize>,
+    /// the current iteration
+    iteration: usize,
+    /// the number of iterations within which a merged section splitting again is flapping
+    flap_window: usize,
+}
+
+impl Lineage {
+    /// Starts recording with the current sections of the network
+    pub fn new(network: &Network) -> Lineage {
+        let mut lineage = Lineage {
+            records: Vec::new(),
+            living: BTreeMap::new(),
+            iteration: 0,
+            flap_window: network.params().flap_window,
+        };
+        for prefix in network.sections().keys() {
+            let _ = lineage.create(*prefix, Cause::Start, Vec::new());
+        }
+        lineage
+    }
+
+    fn create(&mut self, prefix: Prefix, origin: Cause, parents: Vec<usize>) -> usize {
+        let index = self.records.len();
+        for &parent in &parents {
+            self.records[parent].children.push(index);
+        }
+        self.records.push(SectionRecord {
+            prefix,
+            created: self.iteration,
+            origin,
+            parents,
+            children: Vec::new(),
+            destroyed: None,
+        });
+        let _ = self.living.insert(prefix, index);
+        index
+    }
+
+    fn destroy(&mut self, prefix: Prefix, cause: Cause) -> Option<usize> {
+        let index = self.living.remove(&prefix)?;
+        self.records[index].destroyed = Some((self.iteration, cause));
+        Some(index)
+    }
+
+    /// Returns the lifetimes of the sections destroyed by `cause`
+    pub fn lifetimes(&self, cause: Cause) -> Vec<usize> {
+        self.records
+            .iter()
+            .filter(|record| record.destroyed.map(|(_, c)| c) == Some(cause))
+            .filter_map(|record| record.lifetime())
+            .collect()
+    }
+
+    /// Returns the lifetimes of the sections created by a merge and destroyed by a split
+    pub fn resplit_delays(&self) -> Vec<usize> {
+        self.records
+            .iter()
+            .filter(|record| record.origin == Cause::Merge)
+            .filter(|record| record.destroyed.map(|(_, c)| c) == Some(Cause::Split))
+            .filter_map(|record| record.lifetime())
+            .collect()
+    }
+
+    /// Returns the genealogy as CSV, one line per section. Parents and children are given by
+    /// their ids, separated by spaces.
+    pub fn to_csv(&self) -> String {
+        let ids = |indices: &[usize]| {
+            indices.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(" ")
+        };
+        let mut lines = vec!["id,prefix,created,origin,parents,destroyed,reason,children".to_owned()];
+        lines.extend(self.records.iter().enumerate().map(|(id, record)| {
+            let (destroyed, reason) = match record.destroyed {
+                Some((iteration, cause)) => (iteration.to_string(), cause.to_string()),
+                None => (String::new(), String::new()),
+            };
+            format!(
+                "{},{},{},{},{},{},{},{}",
+                id,
+                record.prefix.label(),
+                record.created,
+                record.origin,
+                ids(&record.parents),
+                destroyed,
+                reason,
+                ids(&record.children)
+            )
+        }));
+        lines.join("\n") + "\n"
+    }
+}
+
+impl Observer for Lineage {
+    fn split_finished(&mut self, prefix: Prefix, children: (Prefix, Prefix)) {
+        if let Some(parent) = self.destroy(prefix, Cause::Split) {
+            let _ = self.create(children.0, Cause::Split, vec![parent]);
+            let _ = self.create(children.1, Cause::Split, vec![parent]);
+        }
+    }
+
+    fn merge_finished(&mut self, prefix: Prefix, sections: &[Prefix]) {
+        let parents = sections
+            .iter()
+            .filter_map(|section| self.destroy(*section, Cause::Merge))
+            .collect();
+        let _ = self.create(prefix, Cause::Merge, parents);
+    }
+
+    fn iteration_end(&mut self, iteration: usize, _network: &Network) {
+        self.iteration = iteration + 1;
+    }
+
+    fn measurement_started(&mut self, iteration: usize) {
+        // The genealogy starts over from the existing sections
+        self.iteration = iteration;
+        self.records.clear();
+        for prefix in mem::take(&mut self.living).into_keys() {
+            let _ = self.create(prefix, Cause::Start, Vec::new());
+        }
+    }
+}
+
+// Display lifetime statistics as a markdown table
+impl fmt::Display for Lineage {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let flapping = self
+            .records
+            .iter()
+            .filter(|record| record.is_flapping(self.flap_window))
+            .count();
+        writeln!(fmt, "|    Metrics        |  Values  |")?;
+        writeln!(fmt, "|:------------------|---------:|")?;
+        writeln!(fmt, "| Sections recorded | {:>8} |", self.records.len())?;
+        writeln!(fmt, "| Flapping sections | {:>8} |", flapping)?;
+        writeln!(fmt, "| Flap window       | {:>8} |", self.flap_window)?;
+        writeln!(fmt)?;
+        writeln!(fmt, "| Section lifetime {}", Stats::<usize>::get_header_line())?;
+        writeln!(fmt, "|:-----------------{}", Stats::<usize>::get_separator_line())?;
+        writeln!(fmt, "| Ended by split   | {}", Stats::new(&self.lifetimes(Cause::Split)))?;
+        writeln!(fmt, "| Ended by merge   | {}", Stats::new(&self.lifetimes(Cause::Merge)))?;
+        writeln!(fmt, "| Merge to split   | {}", Stats::new(&self.resplit_delays()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use params::test_params;
+
+    fn prefix(bits: &str) -> Prefix {
+        bits.parse().unwrap()
+    }
+
+    #[test]
+    fn genealogy() {
+        let network = Network::new(test_params());
+        let mut lineage = Lineage::new(&network);
+        // () splits at 10, 1 splits at 110, its halves merge back at 510 and split again at 1010
+        lineage.iteration_end(9, &network);
+        lineage.split_finished(Prefix::empty(), (prefix("0"), prefix("1")));
+        lineage.iteration_end(109, &network);
+        lineage.split_finished(prefix("1"), (prefix("10"), prefix("11")));
+        lineage.iteration_end(509, &network);
+        lineage.merge_finished(prefix("1"), &[prefix("10"), prefix("11")]);
+        lineage.iteration_end(1009, &network);
+        lineage.split_finished(prefix("1"), (prefix("10"), prefix("11")));
+
+        assert_eq!(lineage.records.len(), 8);
+        let living: Vec<Prefix> = lineage.living.keys().cloned().collect();
+        assert_eq!(living, vec![prefix("0"), prefix("10"), prefix("11")]);
+        assert_eq!(lineage.records[0].children, vec![1, 2]);
+        assert_eq!(lineage.records[2].children, vec![3, 4]);
+        let merged = &lineage.records[5];
+        assert_eq!((merged.prefix, merged.created, merged.origin), (prefix("1"), 510, Cause::Merge));
+        assert_eq!(merged.parents, vec![3, 4]);
+        assert_eq!(merged.children, vec![6, 7]);
+        assert_eq!(lineage.records[3].children, vec![5]);
+        assert_eq!(lineage.records[1].destroyed, None);
+
+        assert_eq!(lineage.lifetimes(Cause::Split), vec![10, 100, 500]);
+        assert_eq!(lineage.lifetimes(Cause::Merge), vec![400, 400]);
+        assert_eq!(lineage.resplit_delays(), vec![500]);
+        assert!(merged.is_flapping(500));
+        assert!(!merged.is_flapping(499));
+        assert!(lineage.to_string().contains("| Flapping sections |        1 |"));
+        assert!(lineage.to_string().contains("| Flap window       |     1000 |"));
+        assert!(lineage.to_csv().contains("\n5,1,510,merge,3 4,1010,split,6 7\n"));
+
+        // the measurement starts over from the existing sections
+        lineage.measurement_started(2000);
+        assert_eq!(lineage.records.len(), 3);
+        assert!(lineage
+            .records
+            .iter()
+            .all(|record| record.origin == Cause::Start && record.created == 2000));
+    }
+}
@@ -1,8 +1,15 @@
 pub mod churn;
+pub mod dot;
+pub mod flows;
+pub mod history;
+pub mod lineage;
 pub mod prefix;
 pub mod node;
 pub mod network;
+pub mod observer;
+pub mod output;
 pub mod section;
+pub mod snapshot;
 
 /// Determines the numbers of the elders in every section
 pub const GROUP_SIZE: usize = 8;
@@ -12,4 +19,5 @@ pub const GROUP_SIZE: usize = 8;
 /// GROUP_SIZE + BUFFER nodes
 pub const BUFFER: usize = 3;
 
-pub use self::network::{Network, NetworkStructure};
+pub use self::network::Network;
+pub use self::output::NetworkStructure;
@@ -1,14 +1,16 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
 use std::mem;
 use std::iter::{Iterator, Sum};
 use std::f64;
-use random::{random, shuffle};
-use network::prefix::Prefix;
+use random::{random, random_range, sample};
+use network::prefix::{Name, Prefix};
 use network::node::Node;
 use network::section::Section;
 use network::churn::{NetworkEvent, SectionEvent};
-use params::Params;
+use network::observer::Observer;
+use network::output::{NetworkStructure, Output};
+use params::{Admission, AgeDist, DepartureTarget, MassDeparture, Params, RetryMode};
 use stats::Stats;
 
 /// A wrapper struct that handles merges in progress
@@ -49,37 +51,23 @@ impl PendingMerge {
     }
 }
 
-#[derive(Clone, Default)]
-pub struct NetworkStructure {
-    pub size: usize,
-    pub sections: usize,
-    pub complete: usize,
-}
-
-#[derive(Clone, Default)]
-pub struct Output {
-    /// the number of "add" random events
-    pub adds: u64,
-    /// the number of "drop" random events
-    pub drops: u64,
-    /// the distribution of drops by age
-    pub drops_dist: BTreeMap<u8, usize>,
-    /// the number of "rejoin" random events
-    pub rejoins: u64,
-    /// the number of relocations
-    pub relocations: u64,
-    /// the number of rejected nodes
-    pub rejections: u64,
-    /// the total number of churn events
-    pub churn: u64,
-    /// the structure of the network
-    pub network_structure: Vec<NetworkStructure>,
+/// The attempts of a node to join the network
+#[derive(Clone, Copy)]
+struct JoinAttempts {
+    node: Node,
+    /// the number of attempts so far
+    attempts: usize,
+    /// the number of iterations waited since the first attempt
+    waited: usize,
+    /// the number of iterations left before the next attempt
+    wait_left: usize,
+    /// whether a section processed the `Live` event of the current attempt
+    live_processed: bool,
 }
 
 /// The structure representing the whole network
 /// It's a container for sections that simulates all the
 /// churn and communication between them.
-#[derive(Clone)]
 pub struct Network {
     /// all the sections in the network indexed by prefixes
     nodes: BTreeMap<Prefix, Section>,
@@ -93,6 +81,15 @@ pub struct Network {
     params: Params,
     /// Simulation outputs
     output: Output,
+    /// Additional observers notified of the events in the network
+    observers: Vec<Box<dyn Observer>>,
+    /// the joining nodes whose `Live` events are being processed, indexed by name
+    joining: BTreeMap<Name, JoinAttempts>,
+    /// the rejected nodes waiting to try again
+    retrying: Vec<JoinAttempts>,
+    /// the halves of split sections that are still processing the departure of the other half,
+    /// with the Elders of the section they came from
+    split_elders: BTreeMap<Prefix, BTreeSet<Name>>,
 }
 
 impl Network {
@@ -107,9 +104,132 @@ impl Network {
             pending_merges: BTreeMap::new(),
             params,
             output: Default::default(),
+            observers: Vec::new(),
+            joining: BTreeMap::new(),
+            retrying: Vec::new(),
+            split_elders: BTreeMap::new(),
+        }
+    }
+
+    /// Starts a network made of the given sections, which must cover the whole namespace
+    pub fn with_sections<I: IntoIterator<Item = Section>>(params: Params, sections: I) -> Network {
+        let mut network = Network::new(params);
+        network.nodes = sections.into_iter().map(|s| (s.prefix(), s)).collect();
+        network
+    }
+
+    /// Adds an observer that will be notified of the events in the network
+    pub fn add_observer(&mut self, observer: Box<dyn Observer>) {
+        self.observers.push(observer);
+    }
+
+    /// Calls `callback` on the output and then on every observer
+    fn notify<F: FnMut(&mut dyn Observer)>(&mut self, mut callback: F) {
+        callback(&mut self.output);
+        for observer in &mut self.observers {
+            callback(observer.as_mut());
+        }
+    }
+
+    /// Replaces the content of the network with `count` nodes whose ages are drawn from `ages`.
+    /// A prefix is split as long as both its halves would be allowed to split from each other,
+    /// so the nodes end up in balanced sections that will neither merge nor split right away.
+    pub fn populate(&mut self, count: usize, ages: &AgeDist) {
+        self.nodes.clear();
+        self.event_queue.clear();
+        self.pending_merges.clear();
+        let nodes: Vec<Node> = (0..count).map(|_| Node::new(random(), ages.sample(), self.params.random_node_class())).collect();
+        let mut pending = vec![(Prefix::empty(), nodes)];
+        while let Some((prefix, nodes)) = pending.pop() {
+            let section = Section::with_nodes(prefix, nodes.iter().cloned(), None, &self.params);
+            if section.should_split(&self.params) {
+                let prefix0 = prefix.extend(0);
+                let (nodes0, nodes1): (Vec<Node>, Vec<Node>) =
+                    nodes.into_iter().partition(|node| prefix0.matches(node.name()));
+                pending.push((prefix0, nodes0));
+                pending.push((prefix.extend(1), nodes1));
+            } else {
+                info!("Bootstrapped {:?} with {} nodes", prefix, section.len());
+                self.nodes.insert(prefix, section);
+            }
+        }
+    }
+
+    /// Starts the measurement window at iteration `iteration`: the outputs collected so far are
+    /// discarded, so that they don't include the warm-up
+    pub fn start_measurement(&mut self, iteration: usize) {
+        self.notify(|o| o.measurement_started(iteration));
+    }
+
+    /// Calls `callback` on the output and then on every observer, with read access to the network
+    fn notify_with_network<F: FnMut(&mut dyn Observer, &Network)>(&mut self, mut callback: F) {
+        let mut output = mem::take(&mut self.output);
+        let mut observers = mem::take(&mut self.observers);
+        callback(&mut output, self);
+        for observer in &mut observers {
+            callback(observer.as_mut(), self);
+        }
+        self.output = output;
+        self.observers = observers;
+    }
+
+    /// Notifies the output and the observers that an iteration of the simulation ended, and
+    /// sends the `Live` events of the rejected nodes whose wait is over
+    pub fn end_iteration(&mut self, iteration: usize) {
+        self.notify_with_network(|o, network| o.iteration_end(iteration, network));
+        for attempts in &mut self.retrying {
+            attempts.wait_left -= 1;
+            attempts.waited += 1;
+        }
+        let (ready, waiting) = self.retrying.drain(..).partition(|attempts| attempts.wait_left == 0);
+        self.retrying = waiting;
+        for mut attempts in ready {
+            attempts.attempts += 1;
+            if self.params.retry.map(|retry| retry.mode) == Some(RetryMode::Reroll) {
+                attempts.node = attempts.node.with_name(random());
+            }
+            attempts.live_processed = false;
+            let node = attempts.node;
+            let prefix = self.prefix_for_node(node);
+            info!("Node {:?} tries to join again", node);
+            self.notify(|o| o.node_retrying(prefix, &node, attempts.attempts));
+            let _ = self.joining.insert(node.name(), attempts);
+            self.event_queue
+                .entry(prefix)
+                .or_default()
+                .push(NetworkEvent::Live(node, true));
         }
     }
 
+    /// Makes a fraction of the nodes targeted by `departure` leave the network at once, by
+    /// sending `Lost` events to their sections. Their churn cascade is processed at once, apart
+    /// from the events of the iteration, so that the merges it causes can be measured.
+    pub fn mass_departure(&mut self, iteration: usize, departure: &MassDeparture) {
+        let candidates = self.nodes.iter().flat_map(|(prefix, section)| {
+            section.nodes().into_iter().map(move |node| (*prefix, node))
+        });
+        let candidates: Vec<(Prefix, Node)> = match departure.target {
+            DepartureTarget::Uniform => candidates.collect(),
+            DepartureTarget::Region(region) => {
+                candidates.filter(|&(_, node)| region.matches(node.name())).collect()
+            }
+            DepartureTarget::Ages(min, max) => candidates
+                .filter(|&(_, node)| node.age() >= min && node.age() <= max)
+                .collect(),
+        };
+        let count = (candidates.len() as f64 * departure.fraction).round() as usize;
+        let departed = sample(candidates, count);
+        info!("Mass departure of {} nodes: {:?}", departed.len(), departure);
+        self.notify_with_network(|o, network| o.mass_departure(iteration, &departed, network));
+        for (prefix, node) in departed {
+            self.event_queue
+                .entry(prefix)
+                .or_default()
+                .push(NetworkEvent::Lost(node.name()));
+        }
+        self.process_events();
+    }
+
     /// Checks whether there are any events in the queues
     fn has_events(&self) -> bool {
         self.event_queue.values().any(|x| !x.is_empty())
@@ -120,6 +240,11 @@ impl Network {
             size: self.nodes.values().map(|x| x.len()).sum(),
             sections: self.nodes.len(),
             complete: self.nodes.values().filter(|x| x.is_complete()).count(),
+            split_requests: self.output.split_requests as usize,
+            splits: self.output.splits as usize,
+            merge_requests: self.output.merge_requests as usize,
+            merges_superseded: self.output.merges_superseded as usize,
+            merges: self.output.merges as usize,
         };
         self.output.network_structure.push(structure);
     }
@@ -128,16 +253,42 @@ impl Network {
     /// back. The responses generate new events and the cycle continues until the queues are empty.
     /// Then. if any pending merges are ready, they are processed, too.
     pub fn process_events(&mut self) {
+        let mut cascade_length = 0;
+        // The network quota is checked against the young nodes at the start of the iteration
+        let young_per_section = match self.params.admission {
+            Admission::NetworkQuota(_) => self.young_per_section(),
+            _ => 0.0,
+        };
         while self.has_events() {
             let queue = mem::replace(&mut self.event_queue, BTreeMap::new());
             for (prefix, events) in queue {
                 let mut section_events = vec![];
+                cascade_length += events.len();
                 for event in events {
                     let params = &self.params;
-                    let result = self.nodes
-                        .get_mut(&prefix)
-                        .map(|section| section.handle_event(event, params))
-                        .unwrap_or_else(Vec::new);
+                    let mut elders_change = None;
+                    let result = match self.nodes.get_mut(&prefix) {
+                        Some(section) => {
+                            if let NetworkEvent::Live(node, _) = event {
+                                if let Some(attempts) = self.joining.get_mut(&node.name()) {
+                                    attempts.live_processed = true;
+                                }
+                            }
+                            let old_elders = section.elder_names().clone();
+                            let result = section.handle_event(event, params, young_per_section);
+                            if *section.elder_names() != old_elders {
+                                elders_change = Some((old_elders, section.elder_names().clone()));
+                            }
+                            result
+                        }
+                        None => Vec::new(),
+                    };
+                    if let Some((old_elders, new_elders)) = elders_change {
+                        // the halves of a split section report their Elders once they settle
+                        if !self.split_elders.contains_key(&prefix) {
+                            self.notify(|o| o.elders_changed(prefix, &old_elders, &new_elders));
+                        }
+                    }
                     section_events.extend(result);
                     if let NetworkEvent::PrefixChange(pfx) = event {
                         if let Some(pending_merge) = self.pending_merges.get_mut(&pfx) {
@@ -150,6 +301,9 @@ impl Network {
                 }
             }
         }
+        for (prefix, old_elders) in mem::take(&mut self.split_elders) {
+            self.split_settled(prefix, &old_elders);
+        }
         let merges_to_finalise: Vec<_> = self.pending_merges
             .iter()
             .filter(|&(_, pm)| pm.is_done())
@@ -157,11 +311,39 @@ impl Network {
             .collect();
         for pfx in merges_to_finalise {
             info!("Finalising a merge into {:?}", pfx);
-            self.output.churn += 1; // counting merge as a single churn event
             let pending_merge = self.pending_merges.remove(&pfx).unwrap().into_map();
+            let old_elders: BTreeSet<Name> = pending_merge
+                .keys()
+                .filter_map(|prefix| self.nodes.get(prefix))
+                .flat_map(|section| section.elder_names().iter().cloned())
+                .collect();
             let mut merged_section = self.merged_section(pending_merge.keys(), true);
             merged_section.recompute_drop_weight(&self.params);
+            let new_elders = merged_section.elder_names().clone();
             self.nodes.insert(merged_section.prefix(), merged_section);
+            let sections: Vec<Prefix> = pending_merge.keys().cloned().collect();
+            self.notify(|o| o.merge_finished(pfx, &sections));
+            if new_elders != old_elders {
+                self.notify(|o| o.elders_changed(pfx, &old_elders, &new_elders));
+            }
+        }
+        if cascade_length > 0 {
+            self.notify(|o| o.cascade_finished(cascade_length));
+        }
+        // The joining nodes that weren't rejected have been accepted, unless their `Live` event
+        // was dropped with the queue of a section that split or merged: it is sent again then
+        for (name, attempts) in mem::take(&mut self.joining) {
+            if attempts.live_processed {
+                self.notify(|o| o.node_joined(&attempts.node, attempts.attempts, attempts.waited));
+            } else {
+                let node = attempts.node;
+                let prefix = self.prefix_for_node(node);
+                self.event_queue
+                    .entry(prefix)
+                    .or_default()
+                    .push(NetworkEvent::Live(node, true));
+                let _ = self.joining.insert(name, attempts);
+            }
         }
         // self.capture_network_structure();
     }
@@ -171,19 +353,39 @@ impl Network {
     fn process_single_event(&mut self, prefix: Prefix, event: SectionEvent) {
         match event {
             SectionEvent::NodeDropped(node) => {
-                self.left_nodes.push(node);
+                let class = &self.params.node_classes[node.class() as usize];
+                // Only the nodes that will come back are kept
+                if class.rejoin_probability >= 1.0 || random::<f64>() < class.rejoin_probability {
+                    self.left_nodes.push(node);
+                }
             }
             SectionEvent::NeedRelocate(node) => {
-                self.relocate(node);
+                self.relocate(prefix, node);
             }
-            SectionEvent::NodeRejected(_) => {
-                self.output.rejections += 1;
+            SectionEvent::NodeRejected(node) => {
+                self.notify(|o| o.node_rejected(prefix, &node));
+                if let Some(mut attempts) = self.joining.remove(&node.name()) {
+                    match self.params.retry {
+                        Some(retry) if attempts.attempts < retry.max_attempts => {
+                            attempts.wait_left = retry.delay;
+                            self.retrying.push(attempts);
+                        }
+                        _ => self.notify(|o| o.node_gave_up(&node, attempts.attempts, attempts.waited)),
+                    }
+                }
             }
             SectionEvent::RequestMerge => {
+                self.notify(|o| o.merge_requested(prefix));
                 self.merge(prefix);
             }
             SectionEvent::RequestSplit => {
+                self.notify(|o| o.split_started(prefix));
+                // a half of a split section splitting again first reports its own Elders
+                if let Some(old_elders) = self.split_elders.remove(&prefix) {
+                    self.split_settled(prefix, &old_elders);
+                }
                 if let Some(section) = self.nodes.remove(&prefix) {
+                    let old_elders = section.elder_names().clone();
                     let ((mut sec0, ev0), (mut sec1, ev1)) = section.split();
                     let _ = self.event_queue.remove(&prefix);
                     self.event_queue
@@ -194,16 +396,27 @@ impl Network {
                         .entry(sec1.prefix())
                         .or_insert_with(Vec::new)
                         .extend(ev1);
+                    let children = (sec0.prefix(), sec1.prefix());
                     sec0.recompute_drop_weight(&self.params);
                     self.nodes.insert(sec0.prefix(), sec0);
                     sec1.recompute_drop_weight(&self.params);
                     self.nodes.insert(sec1.prefix(), sec1);
-                    self.output.churn += 1; // counting the split as one churn event
+                    self.notify(|o| o.split_finished(prefix, children));
+                    let _ = self.split_elders.insert(children.0, old_elders.clone());
+                    let _ = self.split_elders.insert(children.1, old_elders);
                 }
             }
         }
     }
 
+    /// Reports the Elders of the half `prefix` of a split section, which had the Elders
+    /// `old_elders`. Every half gets a new set of Elders, even if it's the same one.
+    fn split_settled(&mut self, prefix: Prefix, old_elders: &BTreeSet<Name>) {
+        if let Some(new_elders) = self.nodes.get(&prefix).map(|section| section.elder_names().clone()) {
+            self.notify(|o| o.elders_changed(prefix, old_elders, &new_elders));
+        }
+    }
+
     /// Returns the section that would be the result of merging sections with the given prefixes.
     /// If `destructive` is true, the sections are actually removed from `self.nodes` to be
     /// combined.
@@ -240,14 +453,18 @@ impl Network {
     /// and prepares queues for churn events to be processed before the merge itself.
     fn merge(&mut self, prefix: Prefix) {
         let merged_pfx = prefix.shorten();
-        if let Some(&compatible_merge) = self.pending_merges
+        let compatible_merges: Vec<Prefix> = self.pending_merges
             .keys()
-            .find(|pfx| pfx.is_compatible_with(&merged_pfx))
-        {
-            if compatible_merge.is_ancestor(&merged_pfx) {
-                return;
-            }
+            .filter(|pfx| pfx.is_compatible_with(&merged_pfx))
+            .cloned()
+            .collect();
+        if compatible_merges.iter().any(|pfx| pfx.is_ancestor(&merged_pfx)) {
+            return;
+        }
+        // Every pending merge into a longer prefix is part of this one
+        for compatible_merge in compatible_merges {
             let _ = self.pending_merges.remove(&compatible_merge);
+            self.notify(|o| o.merge_superseded(compatible_merge, merged_pfx));
         }
         info!("Initiating a merge into {:?}", merged_pfx);
         let prefixes: Vec<_> = self.nodes
@@ -258,6 +475,7 @@ impl Network {
 
         let pending_merge = PendingMerge::from_prefixes(prefixes.iter().cloned());
         self.pending_merges.insert(merged_pfx, pending_merge);
+        self.notify(|o| o.merge_started(merged_pfx, &prefixes));
 
         let merged_section = self.merged_section(prefixes.iter(), false);
         for pfx in prefixes {
@@ -282,13 +500,39 @@ impl Network {
         events
     }
 
+    /// Generates a random churn event in the network. There are three possible kinds:
+    /// node joining, node leaving and node rejoining.
+    /// The probabilities of the first two are given in percent.
+    pub fn random_event(&mut self, probs: (f64, f64)) {
+        let x = random::<f64>() * 100.0;
+        if x < probs.0 {
+            self.add_random_node();
+        } else if x >= probs.0 && x < probs.0 + probs.1 {
+            self.drop_random_node();
+        } else {
+            self.rejoin_random_node();
+        }
+    }
+
     /// Adds a random node to the network by pushing an appropriate event to the queue
     pub fn add_random_node(&mut self) {
-        self.output.adds += 1;
-        self.output.churn += 1;
-        let node = Node::new(random(), self.params.init_age);
+        let node = Node::new(random(), self.params.init_age, self.params.random_node_class());
+        self.add_node(node);
+    }
+
+    /// Adds the given node to the network by pushing an appropriate event to the queue
+    pub fn add_node(&mut self, node: Node) {
         info!("Adding node {:?}", node);
+        let attempts = JoinAttempts {
+            node,
+            attempts: 1,
+            waited: 0,
+            wait_left: 0,
+            live_processed: false,
+        };
+        let _ = self.joining.insert(node.name(), attempts);
         let prefix = self.prefix_for_node(node);
+        self.notify(|o| o.node_joining(prefix, &node));
         self.event_queue
             .entry(prefix)
             .or_insert_with(Vec::new)
@@ -307,28 +551,32 @@ impl Network {
 
     /// Returns the prefix a node should belong to.
     fn prefix_for_node(&self, node: Node) -> Prefix {
+        self.prefix_for_name(node.name())
+    }
+
+    /// Returns the prefix of the section a name belongs to.
+    fn prefix_for_name(&self, name: Name) -> Prefix {
         // Use reverse iterator from node name to get section prefix
-        let max = Prefix::from_name(&node.name());
-        let pfx = self.nodes.range(..max).next_back().map(|(pfx, _)| pfx.clone()).unwrap();
+        let max = Prefix::from_name(&name);
+        let pfx = self.nodes.range(..=max).next_back().map(|(pfx, _)| pfx.clone()).unwrap();
         // Check that the algorithm is correct
         assert!(
-            pfx.matches(node.name()),
+            pfx.matches(name),
             "Section {:?} does not match {:?}!",
             pfx,
-            node.name()
+            name
         );
         pfx
     }
 
     /// Chooses a new section for the given node, generates a new name for it,
     /// increases its age,  and sends a `Live` event to the section.
-    fn relocate(&mut self, node: Node) {
-        self.output.relocations += 1;
-        self.output.churn += 2; // leaving one section and joining another one
-        let (node, neighbour) = {
+    /// `prefix` is the section the node is relocated from.
+    fn relocate(&mut self, prefix: Prefix, node: Node) {
+        let (new_node, src_section, neighbour) = {
             // Choose a complete random name, then get its section and lastly select its weakest neighbour.
             let mut new_node = if random::<f64>() < self.params.distant_relocation_probability {
-                Node::new(random(), node.age())
+                node.with_name(random())
             } else {
                 node.clone()
             };
@@ -372,19 +620,18 @@ impl Network {
                 "Relocating {:?} from {:?} to {:?} as {:?}",
                 node, src_section, neighbour, new_node
             );
-            (new_node, neighbour.clone())
+            (new_node, src_section, neighbour.clone())
         };
+        self.notify(|o| o.node_relocated(prefix, src_section, neighbour, &node, &new_node));
         self.event_queue
             .entry(neighbour)
             .or_insert_with(Vec::new)
-            .push(NetworkEvent::Live(node, true));
+            .push(NetworkEvent::Live(new_node, true));
     }
 
     /// Drops a random node from the network by sending a `Lost` event to the section.
     /// The probability of a given node dropping is weighted based on its age.
     pub fn drop_random_node(&mut self) {
-        self.output.drops += 1;
-        self.output.churn += 1;
         let total_weight = self.total_drop_weight();
         let mut drop = random::<f64>() * total_weight;
         let prefix_and_section = {
@@ -398,47 +645,92 @@ impl Network {
             }
             res
         };
-        if let Some((prefix, section)) = prefix_and_section {
+        let mut dropped = None;
+        if let Some((&prefix, section)) = prefix_and_section {
             let node = {
                 let mut res = None;
                 for n in section.nodes().into_iter() {
-                    if n.drop_probability(self.params.drop_dist) > drop {
+                    if n.drop_probability(&self.params) > drop {
                         res = Some(n);
                         break;
                     }
-                    drop -= n.drop_probability(self.params.drop_dist);
+                    drop -= n.drop_probability(&self.params);
                 }
                 res
             };
             if let Some(node) = node {
-                *self.output.drops_dist.entry(node.age()).or_insert(0) += 1;
-                let name = node.name();
-                info!("Dropping node {:?} from section {:?}", name, prefix);
-                self.event_queue
-                    .entry(*prefix)
-                    .or_insert_with(Vec::new)
-                    .push(NetworkEvent::Lost(name));
+                dropped = Some((prefix, node));
             }
         }
+        self.drop_node_from(dropped);
+    }
+
+    /// Drops the node with the given name by sending a `Lost` event to its section.
+    /// Returns false if there is no such node in the sections.
+    pub fn drop_node(&mut self, name: Name) -> bool {
+        let prefix = self.prefix_for_name(name);
+        let node = self.nodes[&prefix].nodes().into_iter().find(|node| node.name() == name);
+        if node.is_some() {
+            self.drop_node_from(node.map(|node| (prefix, node)));
+        }
+        node.is_some()
+    }
+
+    /// Notifies the drop of a node chosen in section `prefix`, or the absence of a node to drop,
+    /// and sends the `Lost` event
+    fn drop_node_from(&mut self, dropped: Option<(Prefix, Node)>) {
+        self.notify(|o| o.node_dropped(dropped.as_ref().map(|&(prefix, ref node)| (prefix, node))));
+        if let Some((prefix, node)) = dropped {
+            let name = node.name();
+            info!("Dropping node {:?} from section {:?}", name, prefix);
+            self.event_queue
+                .entry(prefix)
+                .or_insert_with(Vec::new)
+                .push(NetworkEvent::Lost(name));
+        }
     }
 
     /// Chooses a random node from among the ones that left the network and gets it to rejoin.
     /// The age of the rejoining node is reduced.
+    /// The order of `left_nodes` doesn't matter, so the chosen node is swapped with the last one
+    /// and popped, which keeps the selection O(1) however many nodes have left.
     pub fn rejoin_random_node(&mut self) {
-        self.output.rejoins += 1;
-        self.output.churn += 1;
-        shuffle(&mut self.left_nodes);
-        if let Some(mut node) = self.left_nodes.pop() {
+        let left_node = if self.left_nodes.is_empty() {
+            None
+        } else {
+            let index = random_range(0, self.left_nodes.len());
+            Some(self.left_nodes.swap_remove(index))
+        };
+        if let Some(mut node) = left_node {
             info!("Rejoining node {:?}", node);
-            node.rejoined(self.params.init_age);
+            let penalty = self.params.node_classes[node.class() as usize].rejoin_penalty;
+            node.rejoined(self.params.init_age, penalty);
+            self.notify(|o| o.node_rejoined(Some(&node)));
             let prefix = self.prefix_for_node(node);
             self.event_queue
                 .entry(prefix)
                 .or_insert_with(Vec::new)
                 .push(NetworkEvent::Live(node, true));
+        } else {
+            self.notify(|o| o.node_rejoined(None));
         }
     }
 
+    /// Returns all the sections in the network indexed by prefixes
+    pub fn sections(&self) -> &BTreeMap<Prefix, Section> {
+        &self.nodes
+    }
+
+    /// Returns the simulation parameters
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    /// Returns the number of nodes that left the network and could rejoin
+    pub fn num_left_nodes(&self) -> usize {
+        self.left_nodes.len()
+    }
+
     pub fn num_sections(&self) -> usize {
         self.nodes.len()
     }
@@ -453,6 +745,58 @@ impl Network {
         result
     }
 
+    /// Returns the age distribution of each node class, indexed like `Params::node_classes`
+    pub fn class_age_distributions(&self) -> Vec<BTreeMap<u8, usize>> {
+        let mut result = vec![BTreeMap::new(); self.params.node_classes.len()];
+        for section in self.nodes.values() {
+            for node in section.nodes() {
+                *result[node.class() as usize].entry(node.age()).or_insert(0) += 1;
+            }
+        }
+        result
+    }
+
+    /// Returns the number of Elders of each node class, indexed like `Params::node_classes`
+    pub fn class_elders(&self) -> Vec<usize> {
+        let mut result = vec![0; self.params.node_classes.len()];
+        for section in self.nodes.values() {
+            for node in section.elders() {
+                result[node.class() as usize] += 1;
+            }
+        }
+        result
+    }
+
+    /// Returns the sizes of the sections grouped by prefix length
+    pub fn section_sizes_by_prefix_len(&self) -> BTreeMap<u8, Vec<usize>> {
+        let mut distribution: BTreeMap<u8, Vec<usize>> = BTreeMap::new();
+        for (pfx, section) in &self.nodes {
+            distribution.entry(pfx.len()).or_default().push(section.len());
+        }
+        distribution
+    }
+
+    /// Returns the sizes of all the sections
+    pub fn section_sizes(&self) -> Vec<usize> {
+        self.nodes.values().map(|s| s.len()).collect()
+    }
+
+    /// Returns the mean number of young nodes (aged `init_age` or less) per section
+    pub fn young_per_section(&self) -> f64 {
+        self.nodes.values().map(|s| s.num_young()).sum::<usize>() as f64 / self.nodes.len() as f64
+    }
+
+    /// Returns the share of the sections refusing young nodes, each section counting for the
+    /// probability that it refuses one
+    pub fn rejection_rate(&self) -> f64 {
+        let young_per_section = self.young_per_section();
+        let rejecting: f64 = self.nodes
+            .values()
+            .map(|s| s.rejection_probability(&self.params, young_per_section))
+            .sum();
+        rejecting / self.nodes.len() as f64
+    }
+
     pub fn complete_sections(&self) -> usize {
         self.nodes.iter().filter(|&(_, s)| s.is_complete()).count()
     }
@@ -484,7 +828,6 @@ impl fmt::Debug for Network {
 impl fmt::Display for Network {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         let sections = self.num_sections();
-        let rejecting = self.nodes.iter().filter(|&(_, s)| s.reject_young_node(&self.params)).count() as f64;
         // Network summary
         try!(writeln!(fmt, "|    Metrics     |  Values  |"));
         try!(writeln!(fmt, "|:---------------|---------:|"));
@@ -493,7 +836,21 @@ impl fmt::Display for Network {
         try!(writeln!(fmt, "| Rejoins        | {:>8} |", self.output.rejoins));
         try!(writeln!(fmt, "| Relocations    | {:>8} |", self.output.relocations));
         try!(writeln!(fmt, "| Rejections     | {:>8} |", self.output.rejections));
+        if self.params.retry.is_some() {
+            writeln!(fmt, "| Retries        | {:>8} |", self.output.retries)?;
+            writeln!(fmt, "| Give-ups       | {:>8} |", self.output.give_ups)?;
+            writeln!(fmt, "| Waiting nodes  | {:>8} |", self.retrying.len())?;
+        }
         try!(writeln!(fmt, "| Churns         | {:>8} |", self.output.churn));
+        writeln!(fmt, "| Split requests | {:>8} |", self.output.split_requests)?;
+        writeln!(fmt, "| Splits         | {:>8} |", self.output.splits)?;
+        writeln!(fmt, "| Merge requests | {:>8} |", self.output.merge_requests)?;
+        writeln!(fmt, "| Superseded     | {:>8} |", self.output.merges_superseded)?;
+        writeln!(fmt, "| Merges         | {:>8} |", self.output.merges)?;
+        writeln!(fmt, "| Multi-level    | {:>8} |", self.output.multi_level_merges)?;
+        writeln!(fmt, "| Elder changes  | {:>8} |", self.output.elder_changes)?;
+        writeln!(fmt, "| DKG rounds     | {:>8} |", self.output.elder_set_changes)?;
+        writeln!(fmt, "| DKG per churn  | {:>8.3} |", self.output.elder_set_changes_per_churn())?;
         try!(writeln!(fmt, "| Sections       | {:>8} |", sections));
         let complete = self.complete_sections();
         if complete != sections {
@@ -501,14 +858,12 @@ impl fmt::Display for Network {
         }
         try!(writeln!(fmt, "| Section nodes  | {:>8} |", usize::sum(self.nodes.values().map(|s| s.len()))));
         try!(writeln!(fmt, "| Left nodes     | {:>8} |", self.left_nodes.len()));
-        try!(writeln!(fmt, "| Rejection rate | {:>7.0}% |", rejecting / sections as f64 * 100.0));
+        writeln!(fmt, "| Rejection rate | {:>7.0}% |", self.rejection_rate() * 100.0)?;
+        writeln!(fmt, "| Growth rate    | {:>8.3} |", self.output.growth_rate())?;
+        writeln!(fmt, "| Rej. fairness  | {:>8.3} |", self.output.rejection_fairness())?;
 
         // Distribution of sections per prefix length
-        let mut distribution : BTreeMap<u8, Vec<usize>> = BTreeMap::new();
-        for (pfx, section) in &self.nodes {
-            let mut entry = distribution.entry(pfx.len()).or_insert(Vec::new());
-            entry.push(section.len());
-        }
+        let distribution = self.section_sizes_by_prefix_len();
         let mut lengths: Vec<u8> = distribution.keys().cloned().collect();
         lengths.sort();
         try!(writeln!(fmt, "| Prefix lengths | {:>8} |", lengths.len()));
@@ -525,11 +880,44 @@ impl fmt::Display for Network {
         try!(writeln!(fmt, "| Density gap    | {:>8.2} |", max_density / min_density));
         try!(writeln!(fmt));
 
-        try!(writeln!(fmt, "| Prefix len {}", Stats::get_header_line()));
-        try!(writeln!(fmt, "|-----------:{}", Stats::get_separator_line()));
+        writeln!(fmt, "| Prefix len {}", Stats::<usize>::get_header_line())?;
+        writeln!(fmt, "|-----------:{}", Stats::<usize>::get_separator_line())?;
         for i in lengths {
-            try!(writeln!(fmt, "| {:>10} | {}", i, Stats::new(distribution.get(&i).unwrap())))
+            try!(writeln!(fmt, "| {:>10} | {}", i, Stats::new(distribution.get(&i).unwrap())));
+        }
+        writeln!(fmt, "|        All | {}", Stats::new(&self.section_sizes()))?;
+        writeln!(fmt)?;
+
+        // Metrics accumulated over every event
+        writeln!(fmt, "| Per-event metric    {}", Stats::<usize>::get_header_line())?;
+        writeln!(fmt, "|:--------------------{}", Stats::<usize>::get_separator_line())?;
+        writeln!(fmt, "| Cascade length      | {}", self.output.cascade_length)?;
+        writeln!(fmt, "| Relocation distance | {}", self.output.relocation_distance)?;
+        writeln!(fmt, "| DKG interval        | {}", self.output.elder_change_interval)?;
+        writeln!(fmt, "| Merge depth         | {}", self.output.merge_depth)?;
+        if self.params.retry.is_some() {
+            writeln!(fmt, "| Join wait time      | {}", self.output.join_wait)?;
+        }
+
+        // Impact of the mass departures
+        if !self.output.departures.is_empty() {
+            writeln!(fmt)?;
+            writeln!(fmt, "| Departure iteration | Departed | Lost groups | Merge requests | Cascade length | Merges | Recovery time |")?;
+            writeln!(fmt, "|--------------------:|---------:|------------:|---------------:|---------------:|-------:|--------------:|")?;
+            for departure in &self.output.departures {
+                writeln!(
+                    fmt,
+                    "| {:>19} | {:>8} | {:>11} | {:>14} | {:>14} | {:>6} | {:>13} |",
+                    departure.iteration,
+                    departure.departed,
+                    departure.lost_groups,
+                    departure.merge_requests,
+                    departure.cascade_length.unwrap_or(0),
+                    departure.merges,
+                    departure.recovery_time.map_or("-".to_owned(), |t| t.to_string())
+                )?;
+            }
         }
-        writeln!(fmt, "|        All | {}", Stats::new(&self.nodes.values().map(|s| s.len()).collect()))
+        Ok(())
     }
 }
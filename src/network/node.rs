@@ -1,15 +1,23 @@
 use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
 use random::random;
 use network::prefix::{Name, Prefix};
-use params::DropDist;
+use params::{DropDist, Params};
 
 pub type Digest = [u8; 32];
 
-/// A node has a name and an age
+/// The id of the next node created
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A node has a name, an age and a class, which is an index in `Params::node_classes`.
+/// Its name changes with relocations, so it also has an id that stays the same for its whole
+/// life.
 #[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Node {
     name: Name,
     age: u8,
+    class: u8,
+    id: u64,
 }
 
 impl fmt::Debug for Node {
@@ -19,14 +27,21 @@ impl fmt::Debug for Node {
 }
 
 impl Node {
-    /// Creates a new node
-    pub fn new(name: u64, age: u8) -> Node {
+    /// Creates a new node, with a new id
+    pub fn new(name: Name, age: u8, class: u8) -> Node {
         Node {
-            name: Name(name),
+            name,
             age,
+            class,
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
         }
     }
 
+    /// Returns the same node under another name
+    pub fn with_name(&self, name: Name) -> Node {
+        Node { name, ..*self }
+    }
+
     /// Generates a relocated name and increases the age by 1
     /// bit parameter indicates in which half of the section the node is relocated
     pub fn relocate(&mut self, prefix: &Prefix, bit: Option<u8>) {
@@ -34,14 +49,14 @@ impl Node {
             None => *prefix,
             Some(bit) => prefix.extend(bit),
         };
-        self.name = prefix.substituted_in(Name(random()));
+        self.name = prefix.substituted_in(random());
         self.age += 1;
     }
 
-    /// Decrement the age, because the node is rejoining
-    pub fn rejoined(&mut self, min_age: u8) {
+    /// Decrement the age by `penalty`, because the node is rejoining
+    pub fn rejoined(&mut self, min_age: u8, penalty: u8) {
         if self.age > min_age {
-            self.age -= 1;
+            self.age = self.age.saturating_sub(penalty).max(min_age);
         }
     }
 
@@ -50,21 +65,34 @@ impl Node {
         self.name
     }
 
+    /// Returns the id
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
     /// Returns the age
     pub fn age(&self) -> u8 {
         self.age
     }
 
+    /// Returns the index of the class of the node
+    pub fn class(&self) -> u8 {
+        self.class
+    }
+
     /// Returns whether the node is an Adult
     pub fn is_adult(&self) -> bool {
         self.age > 4
     }
 
-    /// Returns the weight used in randomly choosing a node to be dropped
-    pub fn drop_probability(&self, dist: DropDist) -> f64 {
-        match dist {
+    /// Returns the weight used in randomly choosing a node to be dropped, according to the drop
+    /// distribution and the factor of its class
+    pub fn drop_probability(&self, params: &Params) -> f64 {
+        let class = &params.node_classes[self.class as usize];
+        let weight = match class.drop_dist.unwrap_or(params.drop_dist) {
             DropDist::RevProp => 10.0 / self.age as f64,
             DropDist::Exponential => 2.0f64.powf(-(self.age as f64)),
-        }
+        };
+        weight * class.drop_factor
     }
 }
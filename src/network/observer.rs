@@ -0,0 +1,158 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::rc::Rc;
+use network::Network;
+use network::node::Node;
+use network::prefix::{Name, Prefix};
+
+/// Instrumentation hooks called by the network while it simulates churn.
+/// Every method has an empty default implementation, so an observer only implements the
+/// callbacks it needs. The network notifies its `Output` first and then the observers added with
+/// `Network::add_observer`, in the order they were added.
+#[allow(unused_variables)]
+pub trait Observer {
+    /// A new node was generated and will try to join section `prefix`
+    fn node_joining(&mut self, prefix: Prefix, node: &Node) {}
+
+    /// A node was refused by section `prefix`
+    fn node_rejected(&mut self, prefix: Prefix, node: &Node) {}
+
+    /// A node rejected before tries to join section `prefix` again, for the `attempt`th time
+    fn node_retrying(&mut self, prefix: Prefix, node: &Node, attempt: usize) {}
+
+    /// A joining node was accepted after `attempts` attempts and `waited` iterations of waiting
+    fn node_joined(&mut self, node: &Node, attempts: usize, waited: usize) {}
+
+    /// A rejected node gave up joining after `attempts` attempts and `waited` iterations of waiting
+    fn node_gave_up(&mut self, node: &Node, attempts: usize, waited: usize) {}
+
+    /// A drop event happened: `dropped` holds the node chosen to leave and its section, or None
+    /// if there was no node to drop
+    fn node_dropped(&mut self, dropped: Option<(Prefix, &Node)>) {}
+
+    /// A rejoin event happened: `node` is the node rejoining (with its reduced age), or None if
+    /// no node had left the network
+    fn node_rejoined(&mut self, node: Option<&Node>) {}
+
+    /// Node `old` of section `from` was relocated to section `to`, where it is now `new`. `via`
+    /// is the section of the name the relocation started from, `to` being either `via` itself or
+    /// one of its neighbours.
+    fn node_relocated(&mut self, from: Prefix, via: Prefix, to: Prefix, old: &Node, new: &Node) {}
+
+    /// The set of Elders of section `prefix` changed from `old` to `new`. Each half of a split
+    /// section reports it once, after processing the departure of the other half, with `old`
+    /// being the Elders of the split section.
+    fn elders_changed(&mut self, prefix: Prefix, old: &BTreeSet<Name>, new: &BTreeSet<Name>) {}
+
+    /// Section `prefix` requested a split
+    fn split_started(&mut self, prefix: Prefix) {}
+
+    /// Section `prefix` was replaced by the two sections `children`
+    fn split_finished(&mut self, prefix: Prefix, children: (Prefix, Prefix)) {}
+
+    /// Section `prefix` requested a merge
+    fn merge_requested(&mut self, prefix: Prefix) {}
+
+    /// Sections `sections` started merging into `prefix`
+    fn merge_started(&mut self, prefix: Prefix, sections: &[Prefix]) {}
+
+    /// The pending merge into `prefix` was abandoned for a merge into its ancestor `by`
+    fn merge_superseded(&mut self, prefix: Prefix, by: Prefix) {}
+
+    /// Sections `sections` were combined into `prefix`
+    fn merge_finished(&mut self, prefix: Prefix, sections: &[Prefix]) {}
+
+    /// The nodes `departed` are about to leave the network at once during iteration `iteration`;
+    /// `network` is still in the state before their departure
+    fn mass_departure(&mut self, iteration: usize, departed: &[(Prefix, Node)], network: &Network) {}
+
+    /// The queues of events became empty after `events` network events were processed by the
+    /// sections
+    fn cascade_finished(&mut self, events: usize) {}
+
+    /// The measurement window starts with iteration `iteration`: what was observed before is
+    /// warm-up and should be discarded
+    fn measurement_started(&mut self, iteration: usize) {}
+
+    /// An iteration of the simulation ended
+    fn iteration_end(&mut self, iteration: usize, network: &Network) {}
+}
+
+/// A shared observer, so that the code that added it to the network can read its results
+impl<T: Observer> Observer for Rc<RefCell<T>> {
+    fn node_joining(&mut self, prefix: Prefix, node: &Node) {
+        self.borrow_mut().node_joining(prefix, node)
+    }
+
+    fn node_rejected(&mut self, prefix: Prefix, node: &Node) {
+        self.borrow_mut().node_rejected(prefix, node)
+    }
+
+    fn node_retrying(&mut self, prefix: Prefix, node: &Node, attempt: usize) {
+        self.borrow_mut().node_retrying(prefix, node, attempt)
+    }
+
+    fn node_joined(&mut self, node: &Node, attempts: usize, waited: usize) {
+        self.borrow_mut().node_joined(node, attempts, waited)
+    }
+
+    fn node_gave_up(&mut self, node: &Node, attempts: usize, waited: usize) {
+        self.borrow_mut().node_gave_up(node, attempts, waited)
+    }
+
+    fn node_dropped(&mut self, dropped: Option<(Prefix, &Node)>) {
+        self.borrow_mut().node_dropped(dropped)
+    }
+
+    fn node_rejoined(&mut self, node: Option<&Node>) {
+        self.borrow_mut().node_rejoined(node)
+    }
+
+    fn node_relocated(&mut self, from: Prefix, via: Prefix, to: Prefix, old: &Node, new: &Node) {
+        self.borrow_mut().node_relocated(from, via, to, old, new)
+    }
+
+    fn elders_changed(&mut self, prefix: Prefix, old: &BTreeSet<Name>, new: &BTreeSet<Name>) {
+        self.borrow_mut().elders_changed(prefix, old, new)
+    }
+
+    fn split_started(&mut self, prefix: Prefix) {
+        self.borrow_mut().split_started(prefix)
+    }
+
+    fn split_finished(&mut self, prefix: Prefix, children: (Prefix, Prefix)) {
+        self.borrow_mut().split_finished(prefix, children)
+    }
+
+    fn merge_requested(&mut self, prefix: Prefix) {
+        self.borrow_mut().merge_requested(prefix)
+    }
+
+    fn merge_started(&mut self, prefix: Prefix, sections: &[Prefix]) {
+        self.borrow_mut().merge_started(prefix, sections)
+    }
+
+    fn merge_superseded(&mut self, prefix: Prefix, by: Prefix) {
+        self.borrow_mut().merge_superseded(prefix, by)
+    }
+
+    fn merge_finished(&mut self, prefix: Prefix, sections: &[Prefix]) {
+        self.borrow_mut().merge_finished(prefix, sections)
+    }
+
+    fn mass_departure(&mut self, iteration: usize, departed: &[(Prefix, Node)], network: &Network) {
+        self.borrow_mut().mass_departure(iteration, departed, network)
+    }
+
+    fn cascade_finished(&mut self, events: usize) {
+        self.borrow_mut().cascade_finished(events)
+    }
+
+    fn measurement_started(&mut self, iteration: usize) {
+        self.borrow_mut().measurement_started(iteration)
+    }
+
+    fn iteration_end(&mut self, iteration: usize, network: &Network) {
+        self.borrow_mut().iteration_end(iteration, network)
+    }
+}
@@ -0,0 +1,301 @@
+use std::collections::{BTreeMap, BTreeSet};
+use network::{Network, GROUP_SIZE};
+use network::node::Node;
+use network::observer::Observer;
+use network::prefix::{Name, Prefix};
+use stats::Accumulator;
+
+#[derive(Clone, Default)]
+pub struct NetworkStructure {
+    pub size: usize,
+    pub sections: usize,
+    pub complete: usize,
+    /// the numbers of split and merge events since the start of the measurement window
+    pub split_requests: usize,
+    pub splits: usize,
+    pub merge_requests: usize,
+    pub merges_superseded: usize,
+    pub merges: usize,
+}
+
+/// The impact of a mass departure on the network
+#[derive(Clone, Default)]
+pub struct DepartureImpact {
+    /// the iteration during which the nodes left
+    pub iteration: usize,
+    /// the number of nodes that left
+    pub departed: usize,
+    /// the number of complete sections left with fewer than GROUP_SIZE Adults
+    pub lost_groups: usize,
+    /// the number of sections before the departure
+    pub sections_before: usize,
+    /// the fraction of complete sections before the departure
+    pub complete_before: f64,
+    /// the number of merges requested by the sections while processing the departure
+    pub merge_requests: usize,
+    /// the number of network events processed by the churn cascade of the departure, including
+    /// the merges it requested
+    pub cascade_length: Option<usize>,
+    /// the number of merges finalised until the network recovered
+    pub merges: usize,
+    /// the number of iterations until the number of sections and the fraction of complete
+    /// sections were back to their values before the departure; None if they never were
+    pub recovery_time: Option<usize>,
+}
+
+/// Returns the fraction of complete sections in the network
+fn complete_fraction(network: &Network) -> f64 {
+    network.complete_sections() as f64 / network.num_sections() as f64
+}
+
+#[derive(Clone, Default)]
+pub struct Output {
+    /// the number of "add" random events
+    pub adds: u64,
+    /// the number of "drop" random events
+    pub drops: u64,
+    /// the distribution of drops by age
+    pub drops_dist: BTreeMap<u8, usize>,
+    /// the number of "rejoin" random events
+    pub rejoins: u64,
+    /// the number of relocations
+    pub relocations: u64,
+    /// the number of rejected nodes
+    pub rejections: u64,
+    /// the number of new attempts to join by rejected nodes
+    pub retries: u64,
+    /// the number of rejected nodes that gave up joining
+    pub give_ups: u64,
+    /// the number of iterations each accepted node waited before joining
+    pub join_wait: Accumulator,
+    /// the total number of churn events
+    pub churn: u64,
+    /// the number of split requests by the sections
+    pub split_requests: u64,
+    /// the number of completed splits
+    pub splits: u64,
+    /// the number of merge requests by the sections
+    pub merge_requests: u64,
+    /// the number of pending merges abandoned for a merge into an ancestor
+    pub merges_superseded: u64,
+    /// the number of finalised merges
+    pub merges: u64,
+    /// the number of finalised merges combining sections more than one level below the
+    /// merged prefix
+    pub multi_level_merges: u64,
+    /// the number of levels between the merged prefix and the longest merged section of each
+    /// finalised merge
+    pub merge_depth: Accumulator,
+    /// the number of nodes that became Elders of their section
+    pub elder_changes: u64,
+    /// the number of changes of the Elders of a section, each of which would require a
+    /// distributed key generation (DKG)
+    pub elder_set_changes: u64,
+    /// the iteration of the last change of the Elders of each section
+    pub last_elder_change: BTreeMap<Prefix, usize>,
+    /// the number of iterations between consecutive changes of the Elders of a section
+    pub elder_change_interval: Accumulator,
+    /// the current iteration
+    pub iteration: usize,
+    /// the iteration at which the measurement window started
+    pub measurement_start: usize,
+    /// the structure of the network, from the start of the measurement window
+    pub network_structure: Vec<NetworkStructure>,
+    /// the number of network events processed by the sections for each churn cascade
+    pub cascade_length: Accumulator,
+    /// the number of bits differing between the source and target prefixes of each relocation
+    pub relocation_distance: Accumulator,
+    /// the impact of each mass departure
+    pub departures: Vec<DepartureImpact>,
+    /// the iteration and the number of nodes in the sections at the end of the first iteration
+    /// of the measurement window
+    pub first_size: Option<(usize, usize)>,
+    /// the iteration and the number of nodes in the sections at the end of the last iteration
+    pub last_size: (usize, usize),
+    /// the number of attempts to join and of rejections of each section
+    pub admissions: BTreeMap<Prefix, (u64, u64)>,
+}
+
+impl Output {
+    /// Returns the mean number of nodes gained by the sections per iteration
+    pub fn growth_rate(&self) -> f64 {
+        match self.first_size {
+            Some((first, size)) if self.last_size.0 > first => {
+                (self.last_size.1 as f64 - size as f64) / (self.last_size.0 - first) as f64
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Returns Jain's fairness index of the rejection rates of the sections that nodes tried to
+    /// join: 1 when they all reject the same share of the nodes, down to 1/n when a single
+    /// section out of n does all the rejections
+    pub fn rejection_fairness(&self) -> f64 {
+        let rates: Vec<f64> = self.admissions
+            .values()
+            .filter(|&&(attempts, _)| attempts > 0)
+            .map(|&(attempts, rejections)| rejections as f64 / attempts as f64)
+            .collect();
+        let sum: f64 = rates.iter().sum();
+        let sum_squares: f64 = rates.iter().map(|rate| rate * rate).sum();
+        if sum_squares > 0.0 {
+            sum * sum / (rates.len() as f64 * sum_squares)
+        } else {
+            1.0
+        }
+    }
+
+    /// Returns the number of changes of the Elders of a section per churn event
+    pub fn elder_set_changes_per_churn(&self) -> f64 {
+        self.elder_set_changes as f64 / self.churn.max(1) as f64
+    }
+}
+
+/// The counters are maintained by observing the network
+impl Observer for Output {
+    fn node_joining(&mut self, prefix: Prefix, _node: &Node) {
+        self.adds += 1;
+        self.churn += 1;
+        self.admissions.entry(prefix).or_default().0 += 1;
+    }
+
+    fn node_rejected(&mut self, prefix: Prefix, _node: &Node) {
+        self.rejections += 1;
+        self.admissions.entry(prefix).or_default().1 += 1;
+    }
+
+    fn node_retrying(&mut self, prefix: Prefix, _node: &Node, _attempt: usize) {
+        self.retries += 1;
+        self.admissions.entry(prefix).or_default().0 += 1;
+    }
+
+    fn node_joined(&mut self, _node: &Node, _attempts: usize, waited: usize) {
+        self.join_wait.add(waited);
+    }
+
+    fn node_gave_up(&mut self, _node: &Node, _attempts: usize, _waited: usize) {
+        self.give_ups += 1;
+    }
+
+    fn node_dropped(&mut self, dropped: Option<(Prefix, &Node)>) {
+        self.drops += 1;
+        self.churn += 1;
+        if let Some((_, node)) = dropped {
+            *self.drops_dist.entry(node.age()).or_insert(0) += 1;
+        }
+    }
+
+    fn node_rejoined(&mut self, _node: Option<&Node>) {
+        self.rejoins += 1;
+        self.churn += 1;
+    }
+
+    fn node_relocated(&mut self, from: Prefix, _via: Prefix, to: Prefix, _old: &Node, _new: &Node) {
+        self.relocations += 1;
+        self.churn += 2; // leaving one section and joining another one
+        self.relocation_distance.add(from.bit_distance(&to));
+    }
+
+    fn elders_changed(&mut self, prefix: Prefix, old: &BTreeSet<Name>, new: &BTreeSet<Name>) {
+        self.elder_changes += new.difference(old).count() as u64;
+        self.elder_set_changes += 1;
+        if let Some(last) = self.last_elder_change.insert(prefix, self.iteration) {
+            self.elder_change_interval.add(self.iteration - last);
+        }
+    }
+
+    fn split_started(&mut self, _prefix: Prefix) {
+        self.split_requests += 1;
+    }
+
+    fn split_finished(&mut self, prefix: Prefix, _children: (Prefix, Prefix)) {
+        self.churn += 1; // counting the split as one churn event
+        self.splits += 1;
+        let _ = self.last_elder_change.remove(&prefix);
+    }
+
+    fn merge_requested(&mut self, _prefix: Prefix) {
+        self.merge_requests += 1;
+        // A departure whose cascade isn't finished caused the request
+        for departure in self.departures.iter_mut().filter(|d| d.cascade_length.is_none()) {
+            departure.merge_requests += 1;
+        }
+    }
+
+    fn merge_superseded(&mut self, _prefix: Prefix, _by: Prefix) {
+        self.merges_superseded += 1;
+    }
+
+    fn merge_finished(&mut self, prefix: Prefix, sections: &[Prefix]) {
+        self.churn += 1; // counting merge as a single churn event
+        self.merges += 1;
+        let depth = sections.iter().map(|section| section.len() - prefix.len()).max().unwrap_or(0);
+        if depth > 1 {
+            self.multi_level_merges += 1;
+        }
+        self.merge_depth.add(depth as usize);
+        for section in sections {
+            let _ = self.last_elder_change.remove(section);
+        }
+        for departure in self.departures.iter_mut().filter(|d| d.recovery_time.is_none()) {
+            departure.merges += 1;
+        }
+    }
+
+    fn mass_departure(&mut self, iteration: usize, departed: &[(Prefix, Node)], network: &Network) {
+        let mut departed_adults = BTreeMap::new();
+        for (prefix, _) in departed.iter().filter(|(_, node)| node.is_adult()) {
+            *departed_adults.entry(prefix).or_insert(0) += 1;
+        }
+        let lost_groups = network
+            .sections()
+            .iter()
+            .filter(|&(prefix, section)| {
+                let adults = section.num_elders() + section.num_adults();
+                let departed = departed_adults.get(prefix).cloned().unwrap_or(0);
+                section.is_complete() && adults - departed < GROUP_SIZE
+            })
+            .count();
+        self.departures.push(DepartureImpact {
+            iteration,
+            departed: departed.len(),
+            lost_groups,
+            sections_before: network.num_sections(),
+            complete_before: complete_fraction(network),
+            // Without departed nodes there is no cascade
+            cascade_length: if departed.is_empty() { Some(0) } else { None },
+            ..Default::default()
+        });
+    }
+
+    fn cascade_finished(&mut self, events: usize) {
+        self.cascade_length.add(events);
+        for departure in self.departures.iter_mut().filter(|d| d.cascade_length.is_none()) {
+            departure.cascade_length = Some(events);
+        }
+    }
+
+    fn iteration_end(&mut self, iteration: usize, network: &Network) {
+        self.iteration = iteration + 1;
+        let size = (iteration, network.section_sizes().iter().sum());
+        if self.first_size.is_none() {
+            self.first_size = Some(size);
+        }
+        self.last_size = size;
+        let complete = complete_fraction(network);
+        let sections = network.num_sections();
+        for departure in self.departures.iter_mut().filter(|d| d.recovery_time.is_none()) {
+            if sections >= departure.sections_before && complete >= departure.complete_before {
+                departure.recovery_time = Some(iteration - departure.iteration);
+            }
+        }
+    }
+
+    fn measurement_started(&mut self, iteration: usize) {
+        *self = Output {
+            measurement_start: iteration,
+            iteration,
+            ..Default::default()
+        };
+    }
+}
@@ -1,46 +1,210 @@
+use std::error::Error;
 use std::fmt;
+use std::str::FromStr;
+use std::ops::{BitAnd, BitOr, BitXor, Not};
+use rand::{Rand, Rng};
 
-/// A helper struct that only has the purpose of pretty-printing debug information
+/// The number of 64-bit words in a name (256 bits, like the XorNames used in routing)
+const NAME_WORDS: usize = 4;
+
+/// A 256-bit XorName. The words are stored most significant first, so that the derived ordering
+/// is the numerical ordering of the names.
 #[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
-pub struct Name(pub u64);
+pub struct Name(pub [u64; NAME_WORDS]);
+
+impl Name {
+    /// The name with all bits cleared
+    pub fn zero() -> Name {
+        Name([0; NAME_WORDS])
+    }
+
+    /// Generate a mask with len highest bits set to 1, for example 11110000 ... 00000000 if
+    /// len == 4
+    pub fn high_bits_mask(len: usize) -> Name {
+        let mut mask = Name::zero();
+        for (i, word) in mask.0.iter_mut().enumerate() {
+            let start = i * 64;
+            *word = if len >= start + 64 {
+                !0
+            } else if len > start {
+                !0 << (64 - (len - start))
+            } else {
+                0
+            };
+        }
+        mask
+    }
+
+    /// Returns the value of the bit at position `bit`, counting from the most significant one
+    pub fn bit(&self, bit: usize) -> bool {
+        self.0[bit / 64] & (1 << (63 - bit % 64)) != 0
+    }
+
+    /// Returns a copy of the name with the bit at position `bit` flipped
+    pub fn with_flipped_bit(mut self, bit: usize) -> Name {
+        self.0[bit / 64] ^= 1 << (63 - bit % 64);
+        self
+    }
+
+    /// Returns the number of leading zeros, i.e. the length of the common prefix of two names
+    /// when applied to their XOR
+    pub fn leading_zeros(&self) -> usize {
+        let mut result = 0;
+        for word in &self.0 {
+            let zeros = word.leading_zeros() as usize;
+            result += zeros;
+            if zeros < 64 {
+                break;
+            }
+        }
+        result
+    }
+}
+
+impl Rand for Name {
+    fn rand<R: Rng>(rng: &mut R) -> Name {
+        let mut name = Name::zero();
+        for word in name.0.iter_mut() {
+            *word = rng.gen();
+        }
+        name
+    }
+}
+
+impl BitXor for Name {
+    type Output = Name;
+    fn bitxor(mut self, other: Name) -> Name {
+        for (word, other) in self.0.iter_mut().zip(other.0.iter()) {
+            *word ^= *other;
+        }
+        self
+    }
+}
+
+impl BitAnd for Name {
+    type Output = Name;
+    fn bitand(mut self, other: Name) -> Name {
+        for (word, other) in self.0.iter_mut().zip(other.0.iter()) {
+            *word &= *other;
+        }
+        self
+    }
+}
+
+impl BitOr for Name {
+    type Output = Name;
+    fn bitor(mut self, other: Name) -> Name {
+        for (word, other) in self.0.iter_mut().zip(other.0.iter()) {
+            *word |= *other;
+        }
+        self
+    }
+}
+
+impl Not for Name {
+    type Output = Name;
+    fn not(mut self) -> Name {
+        for word in self.0.iter_mut() {
+            *word = !*word;
+        }
+        self
+    }
+}
 
+/// Only the first bytes are printed, which is enough to tell names apart in debug logs
 impl fmt::Debug for Name {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         let (b0, b1, b2) = (
-            (self.0 >> 56) as u8,
-            (self.0 >> 48) as u8,
-            (self.0 >> 40) as u8,
+            (self.0[0] >> 56) as u8,
+            (self.0[0] >> 48) as u8,
+            (self.0[0] >> 40) as u8,
         );
         write!(fmt, "{:02x}{:02x}{:02x}...", b0, b1, b2)
     }
 }
 
+/// Displays the whole name as 64 hexadecimal digits
+impl fmt::Display for Name {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        for word in &self.0 {
+            write!(fmt, "{:016x}", word)?;
+        }
+        Ok(())
+    }
+}
+
+/// The error returned when parsing a name from a string that isn't made of 1 to 64 hexadecimal
+/// digits
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseNameError;
+
+impl fmt::Display for ParseNameError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "a name must be made of 1 to 64 hexadecimal digits")
+    }
+}
+
+impl Error for ParseNameError {}
+
+/// Parses the hexadecimal digits of a name, most significant first. Missing trailing digits are
+/// zeros, so "a3" is the name starting with bits 10100011 followed by zeros.
+impl FromStr for Name {
+    type Err = ParseNameError;
+    fn from_str(s: &str) -> Result<Name, ParseNameError> {
+        if s.is_empty() || s.len() > NAME_WORDS * 16 {
+            return Err(ParseNameError);
+        }
+        let mut name = Name::zero();
+        for (i, c) in s.chars().enumerate() {
+            let digit = c.to_digit(16).ok_or(ParseNameError)? as u64;
+            name.0[i / 16] |= digit << (60 - 4 * (i % 16));
+        }
+        Ok(name)
+    }
+}
+
+/// The maximum length of a prefix
+const MAX_PREFIX_LEN: u8 = u8::MAX;
+
 /// A structure representing a network prefix - a simplified version of the Prefix struct from
 /// bits field put before len field so that prefixes are ordered correctly in btree maps
 /// `routing`
+/// The length is stored in a u8, so a prefix can have at most 255 of the 256 bits of a name.
 #[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Prefix {
-    bits: u64,
+    bits: Name,
     len: u8,
 }
 
 impl Prefix {
-    /// Build a prefix from name, with len set to 255
+    /// Build a prefix from name, with len set to 255. The last bit of the name doesn't fit and
+    /// is cleared.
     pub fn from_name(name: &Name) -> Prefix {
-        Prefix { bits: name.0, len: <u8>::max_value() }
+        Prefix {
+            bits: *name & Name::high_bits_mask(MAX_PREFIX_LEN as usize),
+            len: MAX_PREFIX_LEN,
+        }
     }
 
     pub fn empty() -> Prefix {
-        Prefix { bits: 0, len: 0 }
+        Prefix { bits: Name::zero(), len: 0 }
     }
 
+    /// Returns the prefix with `bit` appended. Panics if the prefix already has the maximum
+    /// length.
     pub fn extend(self, bit: u8) -> Prefix {
-        if self.len > 63 {
-            return self;
-        }
-        let bit = (bit as u64 & 1) << (63 - self.len);
+        assert!(
+            self.len < MAX_PREFIX_LEN,
+            "Cannot extend a prefix beyond {} bits",
+            MAX_PREFIX_LEN
+        );
+        let bits = if bit & 1 == 0 {
+            self.bits
+        } else {
+            self.bits.with_flipped_bit(self.len as usize)
+        };
         Prefix {
-            bits: self.bits | bit,
+            bits,
             len: self.len + 1,
         }
     }
@@ -50,19 +214,15 @@ impl Prefix {
     }
 
     // Generate a mask with len highest bits set to 1, for example 11110000 ... 00000000 if len == 4
-    fn len_mask(&self) -> u64 {
-        if self.len == 0 {
-            0
-        } else {
-            (-1i64 as u64) << (64 - self.len)
-        }
+    fn len_mask(&self) -> Name {
+        Name::high_bits_mask(self.len as usize)
     }
 
     pub fn shorten(self) -> Prefix {
         if self.len < 1 {
             return self;
         }
-        let mask = self.len_mask() << 1;
+        let mask = Name::high_bits_mask(self.len as usize - 1);
         Prefix {
             bits: self.bits & mask,
             len: self.len - 1,
@@ -70,19 +230,18 @@ impl Prefix {
     }
 
     pub fn with_flipped_bit(self, bit: u8) -> Prefix {
-        let mask = 1 << (63 - bit);
         Prefix {
-            bits: self.bits ^ mask,
+            bits: self.bits.with_flipped_bit(bit as usize),
             len: self.len,
         }
     }
 
     pub fn matches(&self, name: Name) -> bool {
-        (name.0 & self.len_mask()) ^ self.bits == 0
+        (name & self.len_mask()) ^ self.bits == Name::zero()
     }
 
     pub fn is_ancestor(&self, other: &Prefix) -> bool {
-        self.len <= other.len && self.matches(Name(other.bits))
+        self.len <= other.len && self.matches(other.bits)
     }
 
     #[allow(unused)]
@@ -95,67 +254,315 @@ impl Prefix {
     }
 
     pub fn is_sibling(&self, other: &Prefix) -> bool {
-        if self.len > 0 {
-            (*self).with_flipped_bit(self.len - 1) == *other
-        } else {
-            false
-        }
+        self.sibling() == Some(*other)
     }
 
     pub fn is_neighbour(&self, other: &Prefix) -> bool {
-        let diff = self.bits ^ other.bits;
-        let bit = diff.leading_zeros() as u8;
-        if bit < self.len && bit < other.len {
-            let diff = self.with_flipped_bit(bit).bits ^ other.bits;
-            let bit = diff.leading_zeros() as u8;
-            bit >= self.len || bit >= other.len
+        let bit = (self.bits ^ other.bits).leading_zeros();
+        if bit < self.len as usize && bit < other.len as usize {
+            let diff = self.with_flipped_bit(bit as u8).bits ^ other.bits;
+            let bit = diff.leading_zeros();
+            bit >= self.len as usize || bit >= other.len as usize
         } else {
             false
         }
     }
 
-    pub fn substituted_in(&self, mut name: Name) -> Name {
+    pub fn substituted_in(&self, name: Name) -> Name {
         let mask = self.len_mask();
-        name.0 &= !mask;
-        name.0 |= self.bits;
-        name
+        (name & !mask) | self.bits
     }
 
+    /// Returns the sibling prefix, i.e. the one differing only in the last bit
     #[allow(unused)]
-    pub fn from_str(s: &str) -> Option<Prefix> {
+    pub fn sibling(&self) -> Option<Prefix> {
+        if self.len > 0 {
+            Some(self.with_flipped_bit(self.len - 1))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the two prefixes one bit longer than this one
+    #[allow(unused)]
+    pub fn children(&self) -> (Prefix, Prefix) {
+        (self.extend(0), self.extend(1))
+    }
+
+    /// Returns the longest prefix that is an ancestor of both prefixes
+    #[allow(unused)]
+    pub fn common_ancestor(&self, other: &Prefix) -> Prefix {
+        let len = (self.bits ^ other.bits)
+            .leading_zeros()
+            .min(self.len as usize)
+            .min(other.len as usize);
+        Prefix {
+            bits: self.bits & Name::high_bits_mask(len),
+            len: len as u8,
+        }
+    }
+
+    /// Returns the XOR distance between the lowest names covered by the two prefixes
+    pub fn xor_distance(&self, other: &Prefix) -> Name {
+        self.bits ^ other.bits
+    }
+
+    /// Returns the number of differing bits over the length of the shorter prefix. Neighbours
+    /// are at distance 1 and compatible prefixes at distance 0.
+    pub fn bit_distance(&self, other: &Prefix) -> usize {
+        let diff = (self.bits ^ other.bits) & Name::high_bits_mask(self.len.min(other.len) as usize);
+        diff.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// Returns the lowest name matching the prefix
+    #[allow(unused)]
+    pub fn lower_bound(&self) -> Name {
+        self.bits
+    }
+
+    /// Returns the highest name matching the prefix
+    #[allow(unused)]
+    pub fn upper_bound(&self) -> Name {
+        self.bits | !self.len_mask()
+    }
+
+    /// Returns an iterator over the prefixes of the set that are neighbours of this one
+    #[allow(unused)]
+    pub fn neighbours<'a, I>(&self, prefixes: I) -> impl Iterator<Item = Prefix> + 'a
+    where
+        I: IntoIterator<Item = &'a Prefix>,
+        I::IntoIter: 'a,
+    {
+        let prefix = *self;
+        prefixes
+            .into_iter()
+            .filter(move |pfx| prefix.is_neighbour(pfx))
+            .cloned()
+    }
+
+    /// Returns the bits of the prefix, or "()" for the empty prefix, which has no bits to print
+    pub fn label(&self) -> String {
+        if self.len == 0 {
+            "()".to_owned()
+        } else {
+            self.to_string()
+        }
+    }
+}
+
+/// The error returned when parsing a prefix from a string that isn't made of '0's and '1's, or
+/// that has more bits than a prefix can hold
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParsePrefixError {
+    /// A character other than '0' or '1', with its position in the string
+    InvalidChar { invalid: char, position: usize },
+    /// More than the maximum number of bits, with the length of the string
+    TooLong { len: usize },
+}
+
+impl fmt::Display for ParsePrefixError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParsePrefixError::InvalidChar { invalid, position } => write!(
+                fmt,
+                "invalid character {:?} at position {} in prefix",
+                invalid, position
+            ),
+            ParsePrefixError::TooLong { len } => write!(
+                fmt,
+                "prefix of {} bits is longer than the maximum of {}",
+                len, MAX_PREFIX_LEN
+            ),
+        }
+    }
+}
+
+impl Error for ParsePrefixError {}
+
+impl FromStr for Prefix {
+    type Err = ParsePrefixError;
+    fn from_str(s: &str) -> Result<Prefix, ParsePrefixError> {
+        let len = s.chars().count();
         let mut prefix = Self::empty();
-        for c in s.chars() {
+        for (position, c) in s.chars().enumerate() {
             match c {
+                '0' | '1' if position >= MAX_PREFIX_LEN as usize => {
+                    return Err(ParsePrefixError::TooLong { len });
+                }
                 '0' => {
                     prefix = prefix.extend(0);
                 }
                 '1' => {
                     prefix = prefix.extend(1);
                 }
-                _ => {
-                    return None;
+                invalid => {
+                    return Err(ParsePrefixError::InvalidChar { invalid, position });
                 }
             }
         }
-        Some(prefix)
+        Ok(prefix)
     }
+}
 
-    pub fn to_string(&self) -> String {
-        let mut result = String::new();
+/// Displays the prefix as its bits, for example "0110"
+impl fmt::Display for Prefix {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         for i in 0..self.len {
-            let mask = 1 << (63 - i);
-            if self.bits & mask == 0 {
-                result.push('0');
+            if self.bits.bit(i as usize) {
+                write!(fmt, "1")?;
             } else {
-                result.push('1');
+                write!(fmt, "0")?;
             }
         }
-        result
+        Ok(())
     }
 }
 
 impl fmt::Debug for Prefix {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        write!(fmt, "Prefix({})", self.to_string())
+        write!(fmt, "Prefix({})", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lengths on both sides of the boundaries between the words of a name
+    const BOUNDARY_LENGTHS: [usize; 4] = [63, 64, 65, 255];
+
+    /// Returns the prefix of length `len` with all bits set
+    fn ones(len: usize) -> Prefix {
+        (0..len).fold(Prefix::empty(), |prefix, _| prefix.extend(1))
+    }
+
+    #[test]
+    fn high_bits_mask() {
+        assert_eq!(Name::high_bits_mask(0), Name::zero());
+        assert_eq!(Name::high_bits_mask(63).0, [!1, 0, 0, 0]);
+        assert_eq!(Name::high_bits_mask(64).0, [!0, 0, 0, 0]);
+        assert_eq!(Name::high_bits_mask(65).0, [!0, 1 << 63, 0, 0]);
+        assert_eq!(Name::high_bits_mask(255).0, [!0, !0, !0, !1]);
+        assert_eq!(Name::high_bits_mask(256).0, [!0; NAME_WORDS]);
+    }
+
+    #[test]
+    fn flipped_bit_and_leading_zeros() {
+        assert_eq!(Name::zero().leading_zeros(), 256);
+        for &bit in &BOUNDARY_LENGTHS {
+            let name = Name::zero().with_flipped_bit(bit);
+            assert!(name.bit(bit));
+            assert!(!name.bit(bit - 1));
+            assert_eq!(name.leading_zeros(), bit);
+            assert_eq!(name.with_flipped_bit(bit), Name::zero());
+        }
+    }
+
+    #[test]
+    fn extend_across_words() {
+        for &len in &BOUNDARY_LENGTHS {
+            let prefix = ones(len);
+            assert_eq!(prefix.len() as usize, len);
+            assert!(prefix.matches(!Name::zero()));
+            assert!(!prefix.matches((!Name::zero()).with_flipped_bit(len - 1)));
+            assert!(prefix.matches((!Name::zero()).with_flipped_bit(len)));
+            assert_eq!(prefix.shorten(), ones(len - 1));
+            assert_eq!(prefix.substituted_in(Name::zero()), Name::high_bits_mask(len));
+        }
+        assert_eq!(ones(64).extend(1), ones(65));
+    }
+
+    #[test]
+    #[should_panic(expected = "beyond 255 bits")]
+    fn extend_beyond_max_len() {
+        let _ = ones(255).extend(0);
+    }
+
+    #[test]
+    fn from_name() {
+        let prefix = Prefix::from_name(&!Name::zero());
+        assert_eq!(prefix, ones(255));
+        assert_eq!(prefix.lower_bound(), Name::high_bits_mask(255));
+        assert!(prefix.matches(Name::high_bits_mask(255)));
+        assert!(prefix.matches(!Name::zero()));
+    }
+
+    #[test]
+    fn is_neighbour() {
+        for &len in &BOUNDARY_LENGTHS {
+            let prefix = ones(len);
+            let sibling = prefix.with_flipped_bit(len as u8 - 1);
+            assert!(prefix.is_sibling(&sibling));
+            assert!(prefix.is_neighbour(&sibling));
+            assert!(prefix.is_neighbour(&prefix.with_flipped_bit(0)));
+            assert!(!prefix.is_neighbour(&sibling.with_flipped_bit(0)));
+            assert!(!prefix.is_neighbour(&prefix));
+            // a neighbour of the parent is a neighbour of the child
+            assert!(prefix.is_neighbour(&sibling.shorten().with_flipped_bit(0)));
+        }
+    }
+
+    #[test]
+    fn common_ancestor() {
+        for &len in &BOUNDARY_LENGTHS {
+            let prefix = ones(len);
+            let sibling = prefix.sibling().unwrap();
+            if len < 255 {
+                assert_eq!(prefix.children(), (ones(len).extend(0), ones(len + 1)));
+            }
+            assert_eq!(prefix.common_ancestor(&sibling), ones(len - 1));
+            assert_eq!(prefix.common_ancestor(&ones(len - 1)), ones(len - 1));
+            assert_eq!(prefix.common_ancestor(&prefix), prefix);
+            assert_eq!(prefix.common_ancestor(&prefix.with_flipped_bit(0)), Prefix::empty());
+        }
+    }
+
+    #[test]
+    fn bit_distance() {
+        for &len in &BOUNDARY_LENGTHS {
+            let prefix = ones(len);
+            assert_eq!(prefix.lower_bound(), Name::high_bits_mask(len));
+            assert_eq!(prefix.upper_bound(), !Name::zero());
+            assert_eq!(prefix.bit_distance(&prefix), 0);
+            assert_eq!(prefix.bit_distance(&prefix.sibling().unwrap()), 1);
+            assert_eq!(prefix.bit_distance(&ones(len - 2).extend(0)), 1);
+        }
+        let differing = [0, 63, 64, 65, 254]
+            .iter()
+            .fold(ones(255), |prefix, &bit| prefix.with_flipped_bit(bit));
+        assert_eq!(ones(255).bit_distance(&differing), 5);
+        // only the bits of the shorter prefix count
+        assert_eq!(ones(65).bit_distance(&differing), 3);
+    }
+
+    #[test]
+    fn prefix_round_trip() {
+        for text in &["", "0", "1", "0110"] {
+            assert_eq!(text.parse::<Prefix>().unwrap().to_string(), *text);
+        }
+        for &len in &BOUNDARY_LENGTHS {
+            let text = "10".repeat(len / 2 + 1)[..len].to_owned();
+            let prefix: Prefix = text.parse().unwrap();
+            assert_eq!(prefix.to_string(), text);
+        }
+        assert_eq!(
+            "0120".parse::<Prefix>(),
+            Err(ParsePrefixError::InvalidChar { invalid: '2', position: 2 })
+        );
+        let too_long = "1".repeat(256);
+        assert_eq!(too_long.parse::<Prefix>(), Err(ParsePrefixError::TooLong { len: 256 }));
+        assert_eq!(too_long[..255].parse::<Prefix>(), Ok(ones(255)));
+        assert_eq!(Prefix::empty().label(), "()");
+        assert_eq!(ones(3).label(), "111");
+    }
+
+    #[test]
+    fn name_round_trip() {
+        let name = Name([0x0123_4567_89ab_cdef, !0, 0, 1]);
+        assert_eq!(name.to_string().parse::<Name>(), Ok(name));
+        assert_eq!("a3".parse::<Name>(), Ok(Name([0xa3 << 56, 0, 0, 0])));
+        assert_eq!("".parse::<Name>(), Err(ParseNameError));
+        assert_eq!("g".parse::<Name>(), Err(ParseNameError));
+        assert_eq!("0".repeat(65).parse::<Name>(), Err(ParseNameError));
     }
 }
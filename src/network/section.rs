@@ -1,10 +1,12 @@
 use std::collections::{BTreeMap, BTreeSet};
+use std::collections::hash_map::DefaultHasher;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use network::{BUFFER, GROUP_SIZE};
 use network::prefix::{Name, Prefix};
 use network::node::{Digest, Node};
 use network::churn::{NetworkEvent, SectionEvent};
-use params::{Params, RelocationRate};
+use params::{Admission, ElderSelection, Params, RelocationRate};
 use random::random;
 
 
@@ -56,6 +58,8 @@ pub struct Section {
     /// the names of the Infants (including the Elders, if some of them are Infants during the
     /// network startup phase)
     infants: BTreeSet<Name>,
+    /// the number of young nodes (aged `init_age` or less)
+    young: usize,
     /// are we currently merging?
     merging: bool,
     /// are we currently splitting?
@@ -74,12 +78,32 @@ impl Section {
             elders: BTreeSet::new(),
             adults: BTreeSet::new(),
             infants: BTreeSet::new(),
+            young: 0,
             merging: false,
             splitting: false,
             drop_weight: 0f64,
         }
     }
 
+    /// Creates a section already holding `nodes`, which bypass the admission policy. The Elders
+    /// are `elders` if given, or chosen by the elder selection policy otherwise.
+    pub fn with_nodes<I: IntoIterator<Item = Node>>(
+        prefix: Prefix,
+        nodes: I,
+        elders: Option<BTreeSet<Name>>,
+        params: &Params,
+    ) -> Section {
+        let mut section = Section::new(prefix);
+        for node in nodes {
+            section.insert(node, params);
+        }
+        match elders {
+            Some(elders) => section.elders = elders,
+            None => section.update_elders(params),
+        }
+        section
+    }
+
     /// Returns the number of nodes in the section
     pub fn len(&self) -> usize {
         self.nodes.len()
@@ -102,23 +126,83 @@ impl Section {
                 .all(|n| n.is_adult())
     }
 
-    /// Updates the names of the Elders in the section
-    fn update_elders(&mut self) {
+    /// Updates the names of the Elders in the section, according to the elder selection policy
+    fn update_elders(&mut self, params: &Params) {
         let by_age = self.nodes_by_age();
-        self.elders = by_age
-            .into_iter()
-            .take(GROUP_SIZE)
-            .filter(|n| n.is_adult())
-            .map(|n| n.name())
-            .collect();
+        self.elders = match params.elder_selection {
+            ElderSelection::Oldest => by_age
+                .into_iter()
+                .take(GROUP_SIZE)
+                .filter(|n| n.is_adult())
+                .map(|n| n.name())
+                .collect(),
+            ElderSelection::HashTieBreak => {
+                let mut adults: Vec<_> = by_age.into_iter().filter(|n| n.is_adult()).collect();
+                let total_xor = adults.iter().fold(Name::zero(), |total, node| total ^ node.name());
+                adults.sort_by_key(|node| (!node.age(), node.name() ^ total_xor));
+                adults.into_iter().take(GROUP_SIZE).map(|n| n.name()).collect()
+            }
+            ElderSelection::Sticky => {
+                let (mut elders, candidates): (Vec<_>, Vec<_>) = by_age
+                    .into_iter()
+                    .filter(|n| n.is_adult())
+                    .partition(|n| self.elders.contains(&n.name()));
+                let mut candidates = candidates.into_iter();
+                // The oldest candidates take the free seats
+                while elders.len() < GROUP_SIZE {
+                    match candidates.next() {
+                        Some(node) => elders.push(node),
+                        None => break,
+                    }
+                }
+                // Then they only replace the youngest Elder if they are strictly older
+                for candidate in candidates {
+                    let (index, youngest) = elders
+                        .iter()
+                        .enumerate()
+                        .min_by_key(|&(_, n)| n.age())
+                        .map(|(index, n)| (index, *n))
+                        .unwrap();
+                    if candidate.age() <= youngest.age() {
+                        break;
+                    }
+                    elders[index] = candidate;
+                }
+                elders.into_iter().map(|n| n.name()).collect()
+            }
+            ElderSelection::Random => {
+                let (elders, mut candidates): (Vec<_>, Vec<_>) = by_age
+                    .into_iter()
+                    .filter(|n| n.is_adult())
+                    .map(|n| n.name())
+                    .partition(|name| self.elders.contains(name));
+                // The candidates are shuffled by hashing their names with the prefix and the
+                // names of all the members, so that the same members always choose the same
+                // Elders, as when a merge is previewed and then carried out
+                let mut hasher = DefaultHasher::new();
+                (self.prefix.lower_bound().0, self.prefix.len()).hash(&mut hasher);
+                for name in self.nodes.keys() {
+                    name.0.hash(&mut hasher);
+                }
+                let seed = hasher.finish();
+                candidates.sort_by_key(|name| {
+                    let mut hasher = DefaultHasher::new();
+                    (seed, name.0).hash(&mut hasher);
+                    hasher.finish()
+                });
+                let free_seats = GROUP_SIZE.saturating_sub(elders.len());
+                elders.into_iter().chain(candidates.into_iter().take(free_seats)).collect()
+            }
+        };
     }
 
     /// Processes a network event passed to the section and responds with appropriate section
-    /// events
-    pub fn handle_event(&mut self, event: NetworkEvent, params: &Params) -> Vec<SectionEvent> {
+    /// events. `young_per_section` is the mean number of young nodes per section over the
+    /// network, which network-wide admission quotas depend on.
+    pub fn handle_event(&mut self, event: NetworkEvent, params: &Params, young_per_section: f64) -> Vec<SectionEvent> {
         let mut events = vec![];
         let other_event = match event {
-            NetworkEvent::Live(node, _) => self.add(node, params),
+            NetworkEvent::Live(node, _) => self.add(node, params, young_per_section),
             NetworkEvent::Relocated(node) | NetworkEvent::Gone(node) => self.relocate(node.name(), params),
             NetworkEvent::Lost(name) => self.remove(name, params),
             NetworkEvent::PrefixChange(p) => {
@@ -178,8 +262,8 @@ impl Section {
             if cand.len() <= 1 {
                 cand.first().cloned()
             } else {
-                let total_xor = cand.iter().fold(0, |total, node| total ^ node.name().0);
-                cand.sort_by_key(|node| node.name().0 ^ total_xor);
+                let total_xor = cand.iter().fold(Name::zero(), |total, node| total ^ node.name());
+                cand.sort_by_key(|node| node.name() ^ total_xor);
                 cand.first().cloned()
             }
         })
@@ -209,11 +293,34 @@ impl Section {
         }
     }
 
-    // Indicates if the section would currently refuse a young node
-    pub fn reject_young_node(&self, params: &Params) -> bool {
-        params.max_young != 0
-            && self.nodes.values().filter(|n| n.age() <= params.init_age).count() >= params.max_young
-            && self.is_complete()
+    /// Returns the probability that the section currently refuses a young node, according to
+    /// the admission policy
+    pub fn rejection_probability(&self, params: &Params, young_per_section: f64) -> f64 {
+        if !self.is_complete() {
+            return 0.0;
+        }
+        let reject = match params.admission {
+            Admission::MaxYoung(max) => max != 0 && self.young >= max,
+            Admission::InfantRatio(ratio) => self.infants.len() as f64 >= ratio * self.adults.len() as f64,
+            Admission::TargetSize(size) => self.nodes.len() >= size,
+            Admission::NetworkQuota(quota) => young_per_section >= quota,
+            Admission::Probability(probability) => return 1.0 - probability,
+        };
+        if reject {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    // Indicates if the section would currently refuse a young node, or some of them
+    pub fn reject_young_node(&self, params: &Params, young_per_section: f64) -> bool {
+        self.rejection_probability(params, young_per_section) > 0.0
+    }
+
+    /// Returns the number of young nodes (aged `init_age` or less)
+    pub fn num_young(&self) -> usize {
+        self.young
     }
 
     /// Returns the cumulated drop probabilities over the section nodes
@@ -223,24 +330,29 @@ impl Section {
 
     /// Adapt section drop weight when a node is added or removed
     fn increment_drop_weight(&mut self, node: &Node, params: &Params) {
-        self.drop_weight += node.drop_probability(params.drop_dist);
+        self.drop_weight += node.drop_probability(params);
     }
     fn decrement_drop_weight(&mut self, node: &Node, params: &Params) {
-        self.drop_weight -= node.drop_probability(params.drop_dist);
+        self.drop_weight -= node.drop_probability(params);
     }
 
     /// Recompute section drop weight (after a merge or a split)
     pub fn recompute_drop_weight(&mut self, params: &Params) {
         self.drop_weight = self.nodes
             .iter()
-            .map(|(_, n)| n.drop_probability(params.drop_dist))
+            .map(|(_, n)| n.drop_probability(params))
             .sum();
     }
 
     /// Adds a node to the section and returns whether the event was handled
-    fn add(&mut self, node: Node, params: &Params) -> EventResult {
-        if node.age() == params.init_age && self.reject_young_node(params) {
-            // disallow more than one node aged 1 per section if the section is complete
+    fn add(&mut self, node: Node, params: &Params, young_per_section: f64) -> EventResult {
+        let rejection = if node.age() == params.init_age {
+            self.rejection_probability(params, young_per_section)
+        } else {
+            0.0
+        };
+        if rejection >= 1.0 || (rejection > 0.0 && random::<f64>() < rejection) {
+            // the admission policy limits the young nodes once the section is complete
             // (all elders are adults)
             info!("Node {:?} refused in section {:?}", node, self.prefix);
             return EventResult::HandledWithEvent(SectionEvent::NodeRejected(node));
@@ -252,19 +364,27 @@ impl Section {
             node.name(),
             self.verifying_prefix
         );
+        self.insert(node, params);
+        self.update_elders(params);
+        if !node.is_adult() && self.is_complete() {
+            EventResult::Ignored
+        } else {
+            EventResult::Handled
+        }
+    }
+
+    /// Puts a node in the section without applying the admission policy nor updating the Elders
+    fn insert(&mut self, node: Node, params: &Params) {
         if node.is_adult() {
             self.adults.insert(node.name());
         } else {
             self.infants.insert(node.name());
         }
+        if node.age() <= params.init_age {
+            self.young += 1;
+        }
         self.nodes.insert(node.name(), node);
         self.increment_drop_weight(&node, params);
-        self.update_elders();
-        if !node.is_adult() && self.is_complete() {
-            EventResult::Ignored
-        } else {
-            EventResult::Handled
-        }
     }
 
     /// Removes a node from the section and returns whether the event was handled
@@ -272,9 +392,12 @@ impl Section {
         let node = self.nodes.remove(&name);
         let _ = self.adults.remove(&name);
         let _ = self.infants.remove(&name);
-        self.update_elders();
+        self.update_elders(params);
         if let Some(node) = node {
             self.decrement_drop_weight(&node, params);
+            if node.age() <= params.init_age {
+                self.young -= 1;
+            }
             if !node.is_adult() && self.is_complete() {
                 EventResult::Ignored
             } else {
@@ -291,9 +414,12 @@ impl Section {
         let node = self.nodes.remove(&name);
         let _ = self.adults.remove(&name);
         let _ = self.infants.remove(&name);
-        self.update_elders();
+        self.update_elders(params);
         if let Some(node) = node {
             self.decrement_drop_weight(&node, params);
+            if node.age() <= params.init_age {
+                self.young -= 1;
+            }
             if !node.is_adult() && self.is_complete() {
                 EventResult::Ignored
             } else {
@@ -309,6 +435,31 @@ impl Section {
         self.prefix
     }
 
+    /// Returns the number of Elders
+    pub fn num_elders(&self) -> usize {
+        self.elders.len()
+    }
+
+    /// Returns the number of Adults (excluding the Elders)
+    pub fn num_adults(&self) -> usize {
+        self.adults.iter().filter(|name| !self.elders.contains(name)).count()
+    }
+
+    /// Returns the number of Infants (excluding the Elders)
+    pub fn num_infants(&self) -> usize {
+        self.infants.iter().filter(|name| !self.elders.contains(name)).count()
+    }
+
+    /// Returns whether the section is in the process of merging
+    pub fn is_merging(&self) -> bool {
+        self.merging
+    }
+
+    /// Returns whether the section is in the process of splitting
+    pub fn is_splitting(&self) -> bool {
+        self.splitting
+    }
+
     /// Splits the section into two and generates the corresponding churn events
     pub fn split(mut self) -> (SplitData, SplitData) {
         self.splitting = false;
@@ -324,7 +475,7 @@ impl Section {
         section0.verifying_prefix = prefix0;
         section1.prefix = prefix1;
         section1.verifying_prefix = prefix1;
-        for (name, mut node) in &mut section0.nodes {
+        for (name, node) in &section0.nodes {
             if prefix0.matches(*name) {
                 churn1.push(NetworkEvent::Gone(*node));
             } else if prefix1.matches(*name) {
@@ -362,9 +513,11 @@ impl Section {
         if merged_prefix.len() < result.verifying_prefix.len() {
             result.verifying_prefix = merged_prefix;
         }
-        for (_, mut node) in self.nodes.into_iter().chain(other.nodes.into_iter()) {
-            result.add(node, params);
+        // the nodes are already members of the network, so they bypass the admission policy
+        for (_, node) in self.nodes.into_iter().chain(other.nodes.into_iter()) {
+            result.insert(node, params);
         }
+        result.update_elders(params);
         result
     }
 
@@ -428,6 +581,11 @@ impl Section {
         self.nodes.iter().map(|(_, n)| *n).collect()
     }
 
+    /// Returns the names of the section's Elders
+    pub fn elder_names(&self) -> &BTreeSet<Name> {
+        &self.elders
+    }
+
     /// Returns the section's Elders as `Node`s
     pub fn elders(&self) -> BTreeSet<Node> {
         self.elders
@@ -451,3 +609,97 @@ impl fmt::Debug for Section {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use params::test_params;
+
+    /// Returns the name whose highest byte is `byte`
+    fn name(byte: u64) -> Name {
+        Name([byte << 56, 0, 0, 0])
+    }
+
+    fn names(bytes: &[u64]) -> BTreeSet<Name> {
+        bytes.iter().map(|&byte| name(byte)).collect()
+    }
+
+    /// Returns the parameters with the elder selection policy and a section holding the nodes,
+    /// given as (highest byte of the name, age) pairs
+    fn section(policy: ElderSelection, prefix: &str, nodes: &[(u64, u8)]) -> (Params, Section) {
+        let mut params = test_params();
+        params.elder_selection = policy;
+        let nodes = nodes.iter().map(|&(byte, age)| Node::new(name(byte), age, 0));
+        let section = Section::with_nodes(prefix.parse().unwrap(), nodes, None, &params);
+        (params, section)
+    }
+
+    #[test]
+    fn oldest_and_hash_tie_break() {
+        // 11 is the oldest, the other Adults tie and 13 and 14 are Infants
+        let mut nodes: Vec<(u64, u8)> = (1..13).map(|byte| (byte, 10)).collect();
+        nodes[10].1 = 12;
+        nodes.extend(&[(13, 1), (14, 4)]);
+        let (_, oldest) = section(ElderSelection::Oldest, "", &nodes);
+        assert_eq!(*oldest.elder_names(), names(&[11, 1, 2, 3, 4, 5, 6, 7]));
+        // The names of the Adults XOR to 12, which orders the tied ones 12, 8, 9, 10, 4, 5, 6, 7...
+        let (_, hashed) = section(ElderSelection::HashTieBreak, "", &nodes);
+        assert_eq!(*hashed.elder_names(), names(&[11, 12, 8, 9, 10, 4, 5, 6]));
+    }
+
+    #[test]
+    fn sticky() {
+        let nodes: Vec<(u64, u8)> = (1..9).map(|byte| (byte, 10)).collect();
+        let (params, mut sticky) = section(ElderSelection::Sticky, "", &nodes);
+        assert_eq!(*sticky.elder_names(), names(&[1, 2, 3, 4, 5, 6, 7, 8]));
+        // An Adult as old as the Elders doesn't replace any of them
+        sticky.insert(Node::new(name(0), 10, 0), &params);
+        sticky.update_elders(&params);
+        assert_eq!(*sticky.elder_names(), names(&[1, 2, 3, 4, 5, 6, 7, 8]));
+        // A strictly older one replaces a single Elder
+        sticky.insert(Node::new(name(9), 11, 0), &params);
+        sticky.update_elders(&params);
+        assert_eq!(sticky.elder_names().len(), GROUP_SIZE);
+        assert!(sticky.elder_names().contains(&name(9)));
+        assert!(!sticky.elder_names().contains(&name(0)));
+        let original = names(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(sticky.elder_names().difference(&original).count(), 1);
+        // The seat of a leaving Elder goes to the oldest candidate
+        let _ = sticky.remove(name(9), &params);
+        assert_eq!(sticky.elder_names().len(), GROUP_SIZE);
+        assert!(sticky.elder_names().contains(&name(0)));
+    }
+
+    #[test]
+    fn random_among_adults() {
+        let adults: Vec<(u64, u8)> = (1..13).map(|byte| (byte, 5 + byte as u8)).collect();
+        let mut nodes = adults.clone();
+        nodes.extend(&[(13, 1), (14, 4)]);
+        let (params, mut random) = section(ElderSelection::Random, "", &nodes);
+        let elders = random.elder_names().clone();
+        assert_eq!(elders.len(), GROUP_SIZE);
+        assert!(elders.is_subset(&names(&(1..13).collect::<Vec<_>>())));
+        // The same members choose the same Elders, whatever the order they were added in
+        nodes.reverse();
+        let (_, same) = section(ElderSelection::Random, "", &nodes);
+        assert_eq!(*same.elder_names(), elders);
+        // The Elders that stay keep their seats
+        let leaving = *elders.iter().next().unwrap();
+        let _ = random.remove(leaving, &params);
+        assert_eq!(random.elder_names().len(), GROUP_SIZE);
+        let staying: BTreeSet<Name> = elders.iter().cloned().filter(|&n| n != leaving).collect();
+        assert!(random.elder_names().is_superset(&staying));
+
+        // The preview of a merge chooses the same Elders as the merge itself
+        let (params, section0) = section(ElderSelection::Random, "0", &adults[..6]);
+        let high: Vec<(u64, u8)> = adults[..6]
+            .iter()
+            .map(|&(byte, age)| (byte | 0x80, age))
+            .collect();
+        let (_, section1) = section(ElderSelection::Random, "1", &high);
+        let preview = section0.clone().merge(section1.clone(), &params);
+        let merged = section0.merge(section1, &params);
+        assert_eq!(preview.elder_names().len(), GROUP_SIZE);
+        assert_eq!(preview.elder_names(), merged.elder_names());
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use network::{GROUP_SIZE, Network};
+use network::node::Node;
+use network::prefix::{Name, Prefix};
+use network::section::Section;
+use params::Params;
+
+/// First line of a snapshot
+const HEADER: &str = "# ageing_sim snapshot";
+
+/// Returns the role of a node in its section: "elder", "adult" or "infant"
+fn role(section: &Section, node: &Node) -> &'static str {
+    if section.elder_names().contains(&node.name()) {
+        "elder"
+    } else if node.is_adult() {
+        "adult"
+    } else {
+        "infant"
+    }
+}
+
+/// Returns a human-readable snapshot of the sections of the network. Each section starts with a
+/// "section <prefix>" line, followed by one "<name> <age> <role> <class>" line per node, oldest
+/// first. The nodes that left the network and the events in progress are not part of the
+/// snapshot.
+pub fn export(network: &Network) -> String {
+    let mut snapshot = String::new();
+    let _ = writeln!(snapshot, "{}", HEADER);
+    let _ = writeln!(snapshot, "# section <prefix bits, or {} for the empty prefix>", Prefix::empty().label());
+    let _ = writeln!(snapshot, "#   <name in hexadecimal> <age> <elder|adult|infant> [<class>]");
+    let classes = &network.params().node_classes;
+    for (prefix, section) in network.sections() {
+        let _ = writeln!(snapshot, "section {}", prefix.label());
+        let mut nodes: Vec<Node> = section.nodes().into_iter().collect();
+        nodes.sort_by_key(|node| (!node.age(), node.name()));
+        for node in &nodes {
+            let _ = writeln!(
+                snapshot,
+                "  {} {} {} {}",
+                node.name(),
+                node.age(),
+                role(section, node),
+                classes[node.class() as usize].name
+            );
+        }
+    }
+    snapshot
+}
+
+/// Checks that the prefixes cover the whole namespace without overlapping, by combining
+/// siblings until only the empty prefix is left
+fn check_coverage(prefixes: &BTreeSet<Prefix>) -> Result<(), String> {
+    if let Some((a, b)) = prefixes
+        .iter()
+        .zip(prefixes.iter().skip(1))
+        .find(|&(a, b)| a.is_compatible_with(b))
+    {
+        return Err(format!("sections {} and {} overlap", a.label(), b.label()));
+    }
+    let mut remaining = prefixes.clone();
+    while let Some(&longest) = remaining.iter().max_by_key(|pfx| pfx.len()) {
+        let sibling = match longest.sibling() {
+            Some(sibling) => sibling,
+            // Only the empty prefix is left
+            None => return Ok(()),
+        };
+        if !remaining.remove(&sibling) {
+            return Err(format!("no section covers the names starting with {}", sibling));
+        }
+        let _ = remaining.remove(&longest);
+        let _ = remaining.insert(longest.shorten());
+    }
+    Err("the snapshot has no sections".to_owned())
+}
+
+/// Builds a network from a snapshot written by `export` or by hand, starting with the same
+/// header line. The role and class of a node are optional; the class defaults to the first one. The Elders of a section are the nodes
+/// listed as such, or are chosen by the elder selection policy if no role is given in the
+/// section. The snapshot is rejected if a line is malformed, a class is unknown, a name appears
+/// twice or doesn't match its section, the sections don't cover the whole namespace exactly once,
+/// only some nodes of a section have a role, a role doesn't match the age of the node, or a
+/// section has more than GROUP_SIZE Elders.
+pub fn import(snapshot: &str, params: Params) -> Result<Network, String> {
+    // The nodes of each section, with the role and line they were listed with
+    let mut sections: BTreeMap<Prefix, Vec<(Node, Option<String>, usize)>> = BTreeMap::new();
+    let mut names = BTreeSet::new();
+    let mut current = None;
+    if snapshot.lines().next().map(str::trim) != Some(HEADER) {
+        return Err(format!("line 1: expected the header {:?}", HEADER));
+    }
+    for (number, line) in snapshot.lines().enumerate() {
+        let number = number + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields[..] {
+            ["section", prefix] => {
+                let prefix = if prefix == Prefix::empty().label() {
+                    Prefix::empty()
+                } else {
+                    prefix.parse().map_err(|e| format!("line {}: {}", number, e))?
+                };
+                if sections.insert(prefix, Vec::new()).is_some() {
+                    return Err(format!("line {}: section {} is listed twice", number, prefix.label()));
+                }
+                current = Some(prefix);
+            }
+            [name, age] | [name, age, _] | [name, age, _, _] => {
+                let prefix = current.ok_or_else(|| format!("line {}: node listed before any section", number))?;
+                let name: Name = name.parse().map_err(|e| format!("line {}: {}", number, e))?;
+                let age: u8 = match age.parse() {
+                    Ok(age) if age > 0 => age,
+                    _ => return Err(format!("line {}: the age must be a number from 1 to 255", number)),
+                };
+                let role = fields.get(2);
+                if let Some(role) = role {
+                    if !["elder", "adult", "infant"].contains(role) {
+                        return Err(format!("line {}: the role must be elder, adult or infant", number));
+                    }
+                }
+                let class = match fields.get(3) {
+                    None => 0,
+                    Some(class) => params
+                        .node_classes
+                        .iter()
+                        .position(|c| c.name == *class)
+                        .ok_or_else(|| format!("line {}: unknown node class {}", number, class))?
+                        as u8,
+                };
+                if !prefix.matches(name) {
+                    return Err(format!("line {}: name {} doesn't match section {}", number, name, prefix.label()));
+                }
+                if !names.insert(name) {
+                    return Err(format!("line {}: name {} is listed twice", number, name));
+                }
+                sections
+                    .get_mut(&prefix)
+                    .unwrap()
+                    .push((Node::new(name, age, class), role.map(|role| role.to_string()), number));
+            }
+            _ => {
+                return Err(format!(
+                    "line {}: expected \"section <prefix>\" or \"<name> <age> [<role> [<class>]]\", found {:?}",
+                    number, line
+                ))
+            }
+        }
+    }
+    check_coverage(&sections.keys().cloned().collect())?;
+
+    let mut result = Vec::new();
+    for (prefix, nodes) in sections {
+        let elders = if nodes.iter().all(|(_, role, _)| role.is_none()) {
+            None
+        } else {
+            let mut elders = BTreeSet::new();
+            for &(node, ref role, number) in &nodes {
+                let expected = if node.is_adult() { "adult" } else { "infant" };
+                match role.as_ref().map(|role| role.as_str()) {
+                    None => {
+                        return Err(format!(
+                            "line {}: node {} has no role, unlike other nodes of section {}",
+                            number, node.name(), prefix.label()
+                        ))
+                    }
+                    Some("elder") if node.is_adult() => {
+                        let _ = elders.insert(node.name());
+                    }
+                    Some(role) if role != expected => {
+                        return Err(format!(
+                            "line {}: node {} is listed as {} but its age makes it an {}",
+                            number, node.name(), role, expected
+                        ))
+                    }
+                    Some(_) => (),
+                }
+            }
+            if elders.len() > GROUP_SIZE {
+                return Err(format!(
+                    "section {} has {} Elders, more than {}",
+                    prefix.label(), elders.len(), GROUP_SIZE
+                ));
+            }
+            Some(elders)
+        };
+        result.push(Section::with_nodes(prefix, nodes.into_iter().map(|(node, _, _)| node), elders, &params));
+    }
+    Ok(Network::with_sections(params, result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use params::test_params;
+
+    /// Returns `count` node lines of a snapshot, with names starting with `digit` and ages from 5
+    /// upwards, followed by `suffix`
+    fn nodes(digit: char, count: usize, suffix: &str) -> Vec<String> {
+        (0..count)
+            .map(|i| format!("{}{:x} {}{}", digit, i, 5 + i, suffix))
+            .collect()
+    }
+
+    /// Returns a snapshot of the given sections, each with its node lines
+    fn snapshot(sections: &[(&str, Vec<String>)]) -> String {
+        let mut snapshot = format!("{}\n", HEADER);
+        for &(prefix, ref nodes) in sections {
+            snapshot += &format!("section {}\n", prefix);
+            for node in nodes {
+                snapshot += &format!("  {}\n", node);
+            }
+        }
+        snapshot
+    }
+
+    fn import_error(snapshot: &str) -> String {
+        match import(snapshot, test_params()) {
+            Ok(_) => panic!("snapshot accepted:\n{}", snapshot),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut section1 = nodes('8', 9, "");
+        section1.push("f0 1".to_owned());
+        let text = snapshot(&[("0", nodes('0', 12, "")), ("1", section1)]);
+        let network = import(&text, test_params()).unwrap();
+        assert_eq!(network.num_sections(), 2);
+        assert_eq!(network.section_sizes(), vec![12, 10]);
+
+        let exported = export(&network);
+        assert!(exported.starts_with(HEADER));
+        // names are written with all their digits
+        assert!(exported.contains(&format!("  0b{} 16 elder default\n", "0".repeat(62))));
+        assert!(exported.contains(&format!("  f0{} 1 infant default\n", "0".repeat(62))));
+        let reimported = import(&exported, test_params()).unwrap();
+        assert_eq!(export(&reimported), exported);
+    }
+
+    #[test]
+    fn rejected_snapshots() {
+        let valid = [("0", nodes('0', 3, "")), ("1", nodes('8', 3, ""))];
+        assert!(import(&snapshot(&valid), test_params()).is_ok());
+
+        let without_header = snapshot(&valid).replacen(HEADER, "", 1);
+        assert!(import_error(&without_header).contains("header"));
+
+        let overlapping = [("0", nodes('0', 3, "")), ("01", nodes('4', 3, "")), ("1", vec![])];
+        assert!(import_error(&snapshot(&overlapping)).contains("overlap"));
+
+        let uncovered = [("0", nodes('0', 3, ""))];
+        assert!(import_error(&snapshot(&uncovered)).contains("no section covers"));
+
+        let duplicate = [("0", nodes('0', 3, "")), ("1", nodes('8', 3, "")), ("1", vec![])];
+        assert!(import_error(&snapshot(&duplicate)).contains("listed twice"));
+
+        let mut names = nodes('0', 3, "");
+        names.push("01 7".to_owned());
+        let duplicate = [("0", names), ("1", nodes('8', 3, ""))];
+        assert!(import_error(&snapshot(&duplicate)).contains("name 01"));
+
+        let outside = [("0", nodes('0', 3, "")), ("1", nodes('7', 3, ""))];
+        assert!(import_error(&snapshot(&outside)).contains("doesn't match section 1"));
+
+        let too_many_elders = [("0", nodes('0', GROUP_SIZE + 1, " elder")), ("1", vec![])];
+        assert!(import_error(&snapshot(&too_many_elders)).contains("more than 8"));
+
+        let young_elder = [("0", vec!["00 4 elder".to_owned()]), ("1", vec![])];
+        assert!(import_error(&snapshot(&young_elder)).contains("an infant"));
+
+        let malformed = [("0", vec!["00".to_owned()]), ("1", vec![])];
+        assert!(import_error(&snapshot(&malformed)).contains("line 3"));
+    }
+}
@@ -1,4 +1,6 @@
 use std::str::FromStr;
+use random::random;
+use network::prefix::Prefix;
 
 #[derive(Clone, Copy, Debug)]
 pub enum Strategy {
@@ -44,6 +46,33 @@ impl FromStr for RelocationRate {
     }
 }
 
+/// The rule choosing the Elders of a section among its Adults
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ElderSelection {
+    /// the oldest Adults, ties broken by name
+    Oldest,
+    /// the oldest Adults, ties broken by the names XORed with the names of all the Adults
+    HashTieBreak,
+    /// the oldest Adults, but an Elder is only replaced by a strictly older Adult
+    Sticky,
+    /// random Adults replace the Elders that leave, drawn from a hash of the members so that
+    /// the same members always choose the same Elders
+    Random,
+}
+
+impl FromStr for ElderSelection {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "oldest" => Ok(ElderSelection::Oldest),
+            "hash" => Ok(ElderSelection::HashTieBreak),
+            "sticky" => Ok(ElderSelection::Sticky),
+            "random" => Ok(ElderSelection::Random),
+            _ => Err(()),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum DropDist {
     Exponential,
@@ -62,15 +91,434 @@ impl FromStr for DropDist {
     }
 }
 
+/// A distribution of ages given as relative weights, parsed from "age:weight" pairs separated
+/// by commas, like "5:4,6:2,7:1"
+#[derive(Clone, Debug)]
+pub struct AgeDist(pub Vec<(u8, f64)>);
+
+impl AgeDist {
+    /// Halves the weight of every age, starting with `init_age`, which roughly matches the ages
+    /// produced by the relocations
+    pub fn geometric(init_age: u8) -> AgeDist {
+        AgeDist((0..10).map(|i| (init_age + i, 0.5f64.powi(i as i32))).collect())
+    }
+
+    /// Draws a random age from the distribution
+    pub fn sample(&self) -> u8 {
+        let total: f64 = self.0.iter().map(|&(_, weight)| weight).sum();
+        let mut x = random::<f64>() * total;
+        for &(age, weight) in &self.0 {
+            if x < weight {
+                return age;
+            }
+            x -= weight;
+        }
+        self.0.last().map(|&(age, _)| age).unwrap()
+    }
+}
+
+impl FromStr for AgeDist {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let mut weights = Vec::new();
+        for pair in s.split(',') {
+            let mut fields = pair.trim().splitn(2, ':');
+            let age = fields.next().ok_or(())?.trim().parse().map_err(|_| ())?;
+            let weight: f64 = fields.next().ok_or(())?.trim().parse().map_err(|_| ())?;
+            if age == 0 || weight.is_nan() || weight < 0.0 {
+                return Err(());
+            }
+            weights.push((age, weight));
+        }
+        if weights.iter().all(|&(_, weight)| weight == 0.0) {
+            return Err(());
+        }
+        Ok(AgeDist(weights))
+    }
+}
+
+/// A class of nodes sharing the same reliability, parsed from comma separated fields: the name
+/// (without whitespace, '=' or '.'), then optionally "ratio=<share of the joining nodes>",
+/// "drop=<exp|rev>", "factor=<multiplier of the drop probability>", "rejoin=<probability that a
+/// dropped node comes back>" and "penalty=<age lost when rejoining>". For example "home,ratio=0.8,factor=3,rejoin=0.5".
+#[derive(Clone, Debug)]
+pub struct NodeClass {
+    pub name: String,
+    pub ratio: f64,
+    // The drop distribution of the class; the global one if None
+    pub drop_dist: Option<DropDist>,
+    pub drop_factor: f64,
+    pub rejoin_probability: f64,
+    pub rejoin_penalty: u8,
+}
+
+impl Default for NodeClass {
+    fn default() -> NodeClass {
+        NodeClass {
+            name: "default".to_owned(),
+            ratio: 1.0,
+            drop_dist: None,
+            drop_factor: 1.0,
+            rejoin_probability: 1.0,
+            rejoin_penalty: 1,
+        }
+    }
+}
+
+impl FromStr for NodeClass {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let mut fields = s.split(',').map(|field| field.trim());
+        let name = fields.next().ok_or(())?;
+        // The name is a segment of the dotted metric names of the results
+        if name.is_empty() || name.contains(char::is_whitespace) || name.contains(&['=', '.'][..]) {
+            return Err(());
+        }
+        let mut class = NodeClass {
+            name: name.to_owned(),
+            ..Default::default()
+        };
+        for field in fields {
+            let mut parts = field.splitn(2, '=');
+            let (key, value) = match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) => (key, value),
+                _ => return Err(()),
+            };
+            match key {
+                "ratio" => class.ratio = value.parse().map_err(|_| ())?,
+                "drop" => class.drop_dist = Some(value.parse()?),
+                "factor" => class.drop_factor = value.parse().map_err(|_| ())?,
+                "rejoin" => class.rejoin_probability = value.parse().map_err(|_| ())?,
+                "penalty" => class.rejoin_penalty = value.parse().map_err(|_| ())?,
+                _ => return Err(()),
+            }
+        }
+        let valid = class.ratio >= 0.0 && class.drop_factor > 0.0
+            && (0.0..=1.0).contains(&class.rejoin_probability);
+        if valid {
+            Ok(class)
+        } else {
+            Err(())
+        }
+    }
+}
+
+/// A phase of the churn schedule, parsed from "<iterations>:<add>/<drop>" for constant
+/// probabilities, or "<iterations>:<add>/<drop>-><add>/<drop>" for probabilities ramping linearly
+/// from the first pair to the second one. The probabilities are percentages; the rest of 100 is
+/// the probability of a rejoin.
+#[derive(Clone, Copy, Debug)]
+pub struct Phase {
+    pub iterations: usize,
+    // Add and drop probabilities at the start of the phase
+    pub start: (f64, f64),
+    // Add and drop probabilities at the end of the phase
+    pub end: (f64, f64),
+}
+
+impl Phase {
+    /// Returns the add and drop probabilities at iteration `iteration` of the phase
+    pub fn probabilities(&self, iteration: usize) -> (f64, f64) {
+        let t = if self.iterations > 1 {
+            (iteration.min(self.iterations - 1)) as f64 / (self.iterations - 1) as f64
+        } else {
+            0.0
+        };
+        (
+            self.start.0 + (self.end.0 - self.start.0) * t,
+            self.start.1 + (self.end.1 - self.start.1) * t,
+        )
+    }
+}
+
+/// Parses "<add>/<drop>" percentages, which must add up to at most 100
+fn parse_probabilities(s: &str) -> Result<(f64, f64), ()> {
+    let mut fields = s.splitn(2, '/');
+    let add: f64 = fields.next().ok_or(())?.trim().parse().map_err(|_| ())?;
+    let drop: f64 = fields.next().ok_or(())?.trim().parse().map_err(|_| ())?;
+    if add >= 0.0 && drop >= 0.0 && add + drop <= 100.0 {
+        Ok((add, drop))
+    } else {
+        Err(())
+    }
+}
+
+impl FromStr for Phase {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let mut fields = s.splitn(2, ':');
+        let iterations = fields.next().ok_or(())?.trim().parse().map_err(|_| ())?;
+        let mut ramp = fields.next().ok_or(())?.splitn(2, "->");
+        let start = parse_probabilities(ramp.next().ok_or(())?)?;
+        let end = match ramp.next() {
+            Some(end) => parse_probabilities(end)?,
+            None => start,
+        };
+        if iterations == 0 {
+            return Err(());
+        }
+        Ok(Phase { iterations, start, end })
+    }
+}
+
+/// Where a rejected node retries to join
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RetryMode {
+    /// with the same name, so in the same section unless it has split or merged
+    SameSection,
+    /// with a new random name
+    Reroll,
+}
+
+/// How the nodes rejected by a section retry to join, parsed from comma separated fields
+/// "delay=<iterations between attempts>", "mode=<same|reroll>" and "attempts=<maximum number of
+/// attempts, including the first one>". For example "delay=100,mode=reroll,attempts=5".
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub delay: usize,
+    pub mode: RetryMode,
+    pub max_attempts: usize,
+}
+
+impl FromStr for RetryPolicy {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let mut policy = RetryPolicy {
+            delay: 100,
+            mode: RetryMode::SameSection,
+            max_attempts: 5,
+        };
+        for field in s.split(',').map(|field| field.trim()).filter(|field| !field.is_empty()) {
+            let mut parts = field.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some("delay"), Some(delay)) => policy.delay = delay.parse().map_err(|_| ())?,
+                (Some("mode"), Some("same")) => policy.mode = RetryMode::SameSection,
+                (Some("mode"), Some("reroll")) => policy.mode = RetryMode::Reroll,
+                (Some("attempts"), Some(attempts)) => policy.max_attempts = attempts.parse().map_err(|_| ())?,
+                _ => return Err(()),
+            }
+        }
+        if policy.delay == 0 || policy.max_attempts == 0 {
+            return Err(());
+        }
+        Ok(policy)
+    }
+}
+
+/// The rule deciding whether a complete section accepts a young node (aged `init_age` or
+/// less), parsed from "max-young:<count>", "ratio:<infants per adult>", "target:<size>",
+/// "quota:<young nodes per section>" or "probability:<acceptance probability>". Incomplete
+/// sections accept every node.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Admission {
+    /// reject when the section already has this many young nodes; 0 means no control
+    MaxYoung(usize),
+    /// reject when the section already has this many Infants per Adult
+    InfantRatio(f64),
+    /// reject when the section already has this many nodes
+    TargetSize(usize),
+    /// reject when the network already has this many young nodes per section on average,
+    /// wherever they are
+    NetworkQuota(f64),
+    /// accept with this probability
+    Probability(f64),
+}
+
+impl FromStr for Admission {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let mut parts = s.splitn(2, ':');
+        let (policy, value) = (parts.next(), parts.next().ok_or(())?);
+        let admission = match policy {
+            Some("max-young") => Admission::MaxYoung(value.parse().map_err(|_| ())?),
+            Some("ratio") => Admission::InfantRatio(value.parse().map_err(|_| ())?),
+            Some("target") => Admission::TargetSize(value.parse().map_err(|_| ())?),
+            Some("quota") => Admission::NetworkQuota(value.parse().map_err(|_| ())?),
+            Some("probability") => Admission::Probability(value.parse().map_err(|_| ())?),
+            _ => return Err(()),
+        };
+        match admission {
+            Admission::InfantRatio(value) | Admission::NetworkQuota(value) if value < 0.0 => Err(()),
+            Admission::Probability(value) if !(0.0..=1.0).contains(&value) => Err(()),
+            _ => Ok(admission),
+        }
+    }
+}
+
+/// A criterion ending the simulation before the maximum number of iterations, parsed from
+/// "nodes=<count>", "sections=<count>", "prefix-len=<bits>", "time=<seconds>" or
+/// "steady=<window in iterations>:<relative tolerance>"
+#[derive(Clone, Copy, Debug)]
+pub enum StopCondition {
+    /// the number of nodes in the sections reaches the value
+    Nodes(usize),
+    /// the number of sections reaches the value
+    Sections(usize),
+    /// the longest prefix reaches the length
+    PrefixLen(u8),
+    /// the simulation has run for the number of seconds
+    Time(f64),
+    /// the mean age and the number of sections have stayed within the relative tolerance over
+    /// the window
+    SteadyState { window: usize, tolerance: f64 },
+}
+
+impl FromStr for StopCondition {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let mut parts = s.trim().splitn(2, '=');
+        let (key, value) = match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) => (key, value),
+            _ => return Err(()),
+        };
+        match key {
+            "nodes" => Ok(StopCondition::Nodes(value.parse().map_err(|_| ())?)),
+            "sections" => Ok(StopCondition::Sections(value.parse().map_err(|_| ())?)),
+            "prefix-len" => Ok(StopCondition::PrefixLen(value.parse().map_err(|_| ())?)),
+            "time" => match value.parse() {
+                Ok(seconds) if seconds > 0.0 => Ok(StopCondition::Time(seconds)),
+                _ => Err(()),
+            },
+            "steady" => {
+                let mut fields = value.splitn(2, ':');
+                let window = fields.next().ok_or(())?.parse().map_err(|_| ())?;
+                let tolerance: f64 = fields.next().ok_or(())?.parse().map_err(|_| ())?;
+                if window == 0 || tolerance.is_nan() || tolerance < 0.0 {
+                    return Err(());
+                }
+                Ok(StopCondition::SteadyState { window, tolerance })
+            }
+            _ => Err(()),
+        }
+    }
+}
+
+/// The nodes concerned by a mass departure
+#[derive(Clone, Copy, Debug)]
+pub enum DepartureTarget {
+    /// all the nodes of the network
+    Uniform,
+    /// the nodes whose names start with the prefix (a regional outage)
+    Region(Prefix),
+    /// the nodes whose ages are in the inclusive range
+    Ages(u8, u8),
+}
+
+/// When a mass departure happens
+#[derive(Clone, Copy, Debug)]
+pub enum DepartureSchedule {
+    /// at the start of the given iteration
+    At(usize),
+    /// at the start of any iteration, with the given probability
+    Rate(f64),
+}
+
+/// An event removing a fraction of the targeted nodes at once, parsed from comma separated
+/// fields: the fraction, then optionally "prefix=<bits>" or "ages=<min>-<max>", then "at=<iteration>"
+/// or "rate=<probability per iteration>". For example "0.3,prefix=01,at=50000".
+#[derive(Clone, Copy, Debug)]
+pub struct MassDeparture {
+    pub fraction: f64,
+    pub target: DepartureTarget,
+    pub schedule: DepartureSchedule,
+}
+
+impl FromStr for MassDeparture {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let mut fields = s.split(',').map(|field| field.trim());
+        let fraction: f64 = fields.next().ok_or(())?.parse().map_err(|_| ())?;
+        if !(fraction > 0.0 && fraction <= 1.0) {
+            return Err(());
+        }
+        let mut target = DepartureTarget::Uniform;
+        let mut schedule = None;
+        for field in fields {
+            let mut parts = field.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some("prefix"), Some(bits)) => {
+                    target = DepartureTarget::Region(bits.parse().map_err(|_| ())?);
+                }
+                (Some("ages"), Some(range)) => {
+                    let mut bounds = range.splitn(2, '-');
+                    let min = bounds.next().ok_or(())?.parse().map_err(|_| ())?;
+                    let max = bounds.next().ok_or(())?.parse().map_err(|_| ())?;
+                    if min > max {
+                        return Err(());
+                    }
+                    target = DepartureTarget::Ages(min, max);
+                }
+                (Some("at"), Some(iteration)) => {
+                    schedule = Some(DepartureSchedule::At(iteration.parse().map_err(|_| ())?));
+                }
+                (Some("rate"), Some(rate)) => {
+                    let rate: f64 = rate.parse().map_err(|_| ())?;
+                    if !(0.0..=1.0).contains(&rate) {
+                        return Err(());
+                    }
+                    schedule = Some(DepartureSchedule::Rate(rate));
+                }
+                _ => return Err(()),
+            }
+        }
+        Ok(MassDeparture {
+            fraction,
+            target,
+            schedule: schedule.ok_or(())?,
+        })
+    }
+}
+
+impl MassDeparture {
+    /// Returns whether the departure happens at the start of iteration `iteration`
+    pub fn is_due(&self, iteration: usize) -> bool {
+        match self.schedule {
+            DepartureSchedule::At(at) => at == iteration,
+            DepartureSchedule::Rate(rate) => random::<f64>() < rate,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Params {
     pub init_age: u8,
     pub split_strategy: Strategy,
-    pub max_young: usize,
+    // How complete sections admit young nodes
+    pub admission: Admission,
+    // How sections choose their Elders
+    pub elder_selection: ElderSelection,
     pub iterations: usize,
     pub summary_intervals: usize,
-    pub growth: (u8, u8),
+    // The churn schedule; the last phase goes on until the end of the simulation
+    pub phases: Vec<Phase>,
+    // Number of nodes the network starts with (0 to start from a single empty section)
+    pub bootstrap_nodes: usize,
+    // Ages of the nodes the network starts with
+    pub bootstrap_ages: AgeDist,
+    // Number of iterations run before the measurement window starts
+    pub warmup: usize,
+    // Events removing many nodes at once
+    pub mass_departures: Vec<MassDeparture>,
+    // Classes of nodes with different reliabilities; nodes refer to them by index
+    pub node_classes: Vec<NodeClass>,
+    // Criteria ending the simulation before `iterations`
+    pub stop_conditions: Vec<StopCondition>,
+    // How rejected nodes retry to join; None if they give up at once
+    pub retry: Option<RetryPolicy>,
     pub structure_output_file: Option<String>,
+    pub dot_output_file: Option<String>,
+    pub report_file: Option<String>,
+    pub stats_output_file: Option<String>,
+    pub results_file: Option<String>,
+    pub snapshot_input_file: Option<String>,
+    pub snapshot_output_file: Option<String>,
+    pub lineage_output_file: Option<String>,
+    // A section created by a merge that splits again within this number of iterations is
+    // counted as flapping in the lineage
+    pub flap_window: usize,
+    pub histories_output_file: Option<String>,
+    pub flows_output_file: Option<String>,
+    pub tui: bool,
     pub drop_dist: DropDist,
     pub relocation_rate: RelocationRate,
     // A number between 0 and 1 indicating probability of distant relocation:
@@ -89,3 +537,203 @@ pub struct Params {
     // - ...
     pub relocation_margin: i8,
 }
+
+impl Params {
+    /// Checks the parameters that depend on each other
+    pub fn validate(&self) -> Result<(), String> {
+        if self.warmup > 0 && self.warmup >= self.iterations {
+            return Err(format!(
+                "the warm-up of {} iterations must be shorter than the {} iterations of the simulation",
+                self.warmup, self.iterations
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the index of the phase of iteration `iteration` with the add and drop
+    /// probabilities at that iteration
+    pub fn churn_probabilities(&self, iteration: usize) -> (usize, (f64, f64)) {
+        let mut start = 0;
+        for (i, phase) in self.phases.iter().enumerate() {
+            if iteration < start + phase.iterations || i == self.phases.len() - 1 {
+                return (i, phase.probabilities(iteration - start));
+            }
+            start += phase.iterations;
+        }
+        unreachable!("The schedule has no phases!")
+    }
+
+    /// Draws the class of a new node according to the mix ratios of the classes
+    pub fn random_node_class(&self) -> u8 {
+        if self.node_classes.len() == 1 {
+            return 0;
+        }
+        let total: f64 = self.node_classes.iter().map(|class| class.ratio).sum();
+        let mut x = random::<f64>() * total;
+        for (i, class) in self.node_classes.iter().enumerate() {
+            if x < class.ratio {
+                return i as u8;
+            }
+            x -= class.ratio;
+        }
+        (self.node_classes.len() - 1) as u8
+    }
+}
+
+/// Returns the default parameters of the command line, for the tests building networks
+#[cfg(test)]
+pub fn test_params() -> Params {
+    let phase = Phase {
+        iterations: 100_000,
+        start: (90.0, 7.0),
+        end: (90.0, 7.0),
+    };
+    Params {
+        init_age: 1,
+        split_strategy: Strategy::Complete,
+        admission: Admission::MaxYoung(1),
+        elder_selection: ElderSelection::Oldest,
+        iterations: phase.iterations,
+        summary_intervals: 10_000,
+        phases: vec![phase],
+        bootstrap_nodes: 0,
+        bootstrap_ages: AgeDist::geometric(1),
+        warmup: 0,
+        mass_departures: vec![],
+        node_classes: vec![NodeClass::default()],
+        stop_conditions: vec![],
+        retry: None,
+        structure_output_file: None,
+        dot_output_file: None,
+        report_file: None,
+        stats_output_file: None,
+        results_file: None,
+        snapshot_input_file: None,
+        snapshot_output_file: None,
+        lineage_output_file: None,
+        flap_window: 1000,
+        histories_output_file: None,
+        flows_output_file: None,
+        tui: false,
+        drop_dist: DropDist::Exponential,
+        relocation_rate: RelocationRate::Standard,
+        distant_relocation_probability: 1.0,
+        relocation_margin: 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn age_dist() {
+        let dist: AgeDist = "5:4, 6:2,7:1".parse().unwrap();
+        assert_eq!(dist.0, vec![(5, 4.0), (6, 2.0), (7, 1.0)]);
+        assert_eq!("9:1".parse::<AgeDist>().unwrap().sample(), 9);
+        assert!("0:1".parse::<AgeDist>().is_err());
+        assert!("5:0".parse::<AgeDist>().is_err());
+        assert!("5".parse::<AgeDist>().is_err());
+    }
+
+    #[test]
+    fn mass_departure() {
+        let departure: MassDeparture = "0.3,prefix=01,at=50000".parse().unwrap();
+        assert_eq!(departure.fraction, 0.3);
+        assert!(matches!(departure.target, DepartureTarget::Region(prefix) if prefix.to_string() == "01"));
+        assert!(matches!(departure.schedule, DepartureSchedule::At(50000)));
+        let departure: MassDeparture = "1,ages=5-7,rate=0.001".parse().unwrap();
+        assert!(matches!(departure.target, DepartureTarget::Ages(5, 7)));
+        assert!(matches!(departure.schedule, DepartureSchedule::Rate(rate) if rate == 0.001));
+        assert!("0.3,prefix=01".parse::<MassDeparture>().is_err());
+        assert!("0,at=5".parse::<MassDeparture>().is_err());
+        assert!("0.3,ages=7-5,at=5".parse::<MassDeparture>().is_err());
+    }
+
+    #[test]
+    fn node_class() {
+        let class: NodeClass = "home,ratio=0.8,drop=rev,factor=3,rejoin=0.5,penalty=2".parse().unwrap();
+        assert_eq!(class.name, "home");
+        assert_eq!((class.ratio, class.drop_factor, class.rejoin_probability), (0.8, 3.0, 0.5));
+        assert!(matches!(class.drop_dist, Some(DropDist::RevProp)));
+        assert_eq!(class.rejoin_penalty, 2);
+        let class: NodeClass = "dc".parse().unwrap();
+        assert_eq!((class.ratio, class.rejoin_probability), (1.0, 1.0));
+        assert!("home,rejoin=2".parse::<NodeClass>().is_err());
+        assert!("home,speed=2".parse::<NodeClass>().is_err());
+        assert!("".parse::<NodeClass>().is_err());
+        assert!("home.dc".parse::<NodeClass>().is_err());
+    }
+
+    #[test]
+    fn stop_condition() {
+        assert!(matches!("nodes=5000".parse(), Ok(StopCondition::Nodes(5000))));
+        assert!(matches!("prefix-len=8".parse(), Ok(StopCondition::PrefixLen(8))));
+        assert!(matches!(
+            "steady=1000:0.05".parse(),
+            Ok(StopCondition::SteadyState { window: 1000, tolerance }) if tolerance == 0.05
+        ));
+        assert!("time=0".parse::<StopCondition>().is_err());
+        assert!("steady=0:0.05".parse::<StopCondition>().is_err());
+        assert!("nodes".parse::<StopCondition>().is_err());
+    }
+
+    #[test]
+    fn phase() {
+        let phase: Phase = "1000:90/7".parse().unwrap();
+        assert_eq!((phase.iterations, phase.start, phase.end), (1000, (90.0, 7.0), (90.0, 7.0)));
+        let ramp: Phase = "101:90/5->10/85".parse().unwrap();
+        assert_eq!((ramp.start, ramp.end), ((90.0, 5.0), (10.0, 85.0)));
+        assert_eq!(ramp.probabilities(50), (50.0, 45.0));
+        assert_eq!(ramp.probabilities(1000), (10.0, 85.0));
+        assert!("0:90/7".parse::<Phase>().is_err());
+        assert!("1000:90/20".parse::<Phase>().is_err());
+        assert!("1000:90".parse::<Phase>().is_err());
+    }
+
+    #[test]
+    fn retry_policy() {
+        let policy: RetryPolicy = "delay=10,mode=reroll,attempts=3".parse().unwrap();
+        assert_eq!((policy.delay, policy.mode, policy.max_attempts), (10, RetryMode::Reroll, 3));
+        let policy: RetryPolicy = "".parse().unwrap();
+        assert_eq!((policy.delay, policy.mode, policy.max_attempts), (100, RetryMode::SameSection, 5));
+        assert!("delay=0".parse::<RetryPolicy>().is_err());
+        assert!("mode=other".parse::<RetryPolicy>().is_err());
+    }
+
+    #[test]
+    fn admission() {
+        assert_eq!("max-young:1".parse(), Ok(Admission::MaxYoung(1)));
+        assert_eq!("ratio:0.5".parse(), Ok(Admission::InfantRatio(0.5)));
+        assert_eq!("target:60".parse(), Ok(Admission::TargetSize(60)));
+        assert_eq!("quota:2.5".parse(), Ok(Admission::NetworkQuota(2.5)));
+        assert_eq!("probability:1".parse(), Ok(Admission::Probability(1.0)));
+        assert!("probability:1.5".parse::<Admission>().is_err());
+        assert!("ratio:-1".parse::<Admission>().is_err());
+        assert!("max-young".parse::<Admission>().is_err());
+        assert!("oldest:3".parse::<Admission>().is_err());
+    }
+
+    #[test]
+    fn elder_selection() {
+        assert_eq!("oldest".parse(), Ok(ElderSelection::Oldest));
+        assert_eq!("hash".parse(), Ok(ElderSelection::HashTieBreak));
+        assert_eq!("sticky".parse(), Ok(ElderSelection::Sticky));
+        assert_eq!("random".parse(), Ok(ElderSelection::Random));
+        assert!("youngest".parse::<ElderSelection>().is_err());
+    }
+
+    #[test]
+    fn validate() {
+        let mut params = test_params();
+        assert!(params.validate().is_ok());
+        params.warmup = params.iterations;
+        assert!(params.validate().unwrap_err().contains("warm-up"));
+        params.warmup = params.iterations - 1;
+        assert!(params.validate().is_ok());
+        // without a warm-up, even an empty simulation is valid
+        params.iterations = 0;
+        params.warmup = 0;
+        assert!(params.validate().is_ok());
+    }
+}
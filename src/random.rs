@@ -30,7 +30,6 @@ thread_local! {
 }
 
 /// Get the seed used for the random number generator.
-#[allow(unused)]
 pub fn seed() -> [u32; 4] {
     SEED.with(|seed| *seed)
 }
@@ -46,7 +45,6 @@ pub fn random_range<T: Rand + PartialOrd + SampleRange>(min: T, max: T) -> T {
 }
 
 /// Sample values from an iterator.
-#[allow(unused)]
 pub fn sample<T, I>(iterable: I, amount: usize) -> Vec<T>
 where
     I: IntoIterator<Item = T>,
@@ -62,8 +60,3 @@ where
 {
     sample(iterable, 1).pop()
 }
-
-/// Shuffle the mutable slice in place.
-pub fn shuffle<T>(values: &mut [T]) {
-    WEAK_RNG.with(|rng| rng.borrow_mut().shuffle(values))
-}
@@ -0,0 +1,256 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use network::{Network, NetworkStructure};
+use stats::Stats;
+
+/// Dimensions of the charts, in pixels
+const WIDTH: f64 = 900.0;
+const HEIGHT: f64 = 320.0;
+const MARGIN: f64 = 60.0;
+/// Number of ticks on each axis
+const TICKS: usize = 5;
+/// Time series longer than this are downsampled to keep the page small
+const MAX_POINTS: usize = 1000;
+
+/// A line of a line chart
+struct Series<'a> {
+    label: &'a str,
+    colour: &'a str,
+    points: Vec<(f64, f64)>,
+}
+
+/// Escapes the characters that have a meaning in HTML
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Formats an axis label, without decimals for large values
+fn tick_label(value: f64) -> String {
+    if value.abs() >= 10.0 || value == value.trunc() {
+        format!("{:.0}", value)
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+/// Formats an optional value of a table cell
+fn optional(value: Option<f64>, precision: usize) -> String {
+    value.map_or("None".to_owned(), |value| format!("{:.*}", precision, value))
+}
+
+/// Maps a value from the data range to a pixel coordinate on the x axis
+fn x_pos(x: f64, max_x: f64) -> f64 {
+    MARGIN + x / max_x * (WIDTH - 2.0 * MARGIN)
+}
+
+/// Maps a value from the data range to a pixel coordinate on the y axis
+fn y_pos(y: f64, max_y: f64) -> f64 {
+    HEIGHT - MARGIN - y / max_y * (HEIGHT - 2.0 * MARGIN)
+}
+
+/// Writes the opening tag of a chart together with its title and axes
+fn chart_frame(svg: &mut String, title: &str, max_x: f64, max_y: f64) {
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+         font-family=\"sans-serif\" font-size=\"12\">",
+        WIDTH, HEIGHT
+    );
+    let _ = writeln!(
+        svg,
+        "<text x=\"{}\" y=\"20\" text-anchor=\"middle\" font-size=\"16\">{}</text>",
+        WIDTH / 2.0,
+        escape(title)
+    );
+    let _ = writeln!(
+        svg,
+        "<line x1=\"{0}\" y1=\"{1}\" x2=\"{2}\" y2=\"{1}\" stroke=\"black\"/>\
+         <line x1=\"{0}\" y1=\"{1}\" x2=\"{0}\" y2=\"{3}\" stroke=\"black\"/>",
+        MARGIN,
+        HEIGHT - MARGIN,
+        WIDTH - MARGIN,
+        MARGIN
+    );
+    for i in 0..TICKS + 1 {
+        let y = max_y * i as f64 / TICKS as f64;
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{0}\" y1=\"{1}\" x2=\"{2}\" y2=\"{1}\" stroke=\"#DDDDDD\"/>\
+             <text x=\"{3}\" y=\"{4}\" text-anchor=\"end\">{5}</text>",
+            MARGIN,
+            y_pos(y, max_y),
+            WIDTH - MARGIN,
+            MARGIN - 5.0,
+            y_pos(y, max_y) + 4.0,
+            tick_label(y)
+        );
+    }
+    for i in 0..TICKS + 1 {
+        let x = max_x * i as f64 / TICKS as f64;
+        let _ = writeln!(
+            svg,
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+            x_pos(x, max_x),
+            HEIGHT - MARGIN + 18.0,
+            tick_label(x)
+        );
+    }
+}
+
+/// Renders a line chart of one or more series sharing the same axes
+fn line_chart(title: &str, series: &[Series]) -> String {
+    let points = || series.iter().flat_map(|s| s.points.iter());
+    let max_x = points().map(|p| p.0).fold(1.0, f64::max);
+    let max_y = points().map(|p| p.1).fold(1.0, f64::max);
+    let mut svg = String::new();
+    chart_frame(&mut svg, title, max_x, max_y);
+    for (i, s) in series.iter().enumerate() {
+        let step = (s.points.len() / MAX_POINTS).max(1);
+        let path: Vec<String> = s.points
+            .iter()
+            .step_by(step)
+            .map(|&(x, y)| format!("{:.1},{:.1}", x_pos(x, max_x), y_pos(y, max_y)))
+            .collect();
+        let _ = writeln!(
+            svg,
+            "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\" points=\"{}\"/>",
+            s.colour,
+            path.join(" ")
+        );
+        let legend_y = MARGIN + 15.0 * i as f64;
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{}\" y=\"{}\" width=\"10\" height=\"10\" fill=\"{}\"/>\
+             <text x=\"{}\" y=\"{}\">{}</text>",
+            WIDTH - MARGIN - 180.0,
+            legend_y - 9.0,
+            s.colour,
+            WIDTH - MARGIN - 165.0,
+            legend_y,
+            escape(s.label)
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Renders a bar chart of a distribution indexed by age
+fn bar_chart(title: &str, colour: &str, data: &BTreeMap<u8, usize>) -> String {
+    let max_x = data.keys().last().map_or(1.0, |&age| age as f64 + 1.0);
+    let max_y = data.values().map(|&n| n as f64).fold(1.0, f64::max);
+    let bar_width = (WIDTH - 2.0 * MARGIN) / max_x;
+    let mut svg = String::new();
+    chart_frame(&mut svg, title, max_x, max_y);
+    for (&age, &count) in data {
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\">\
+             <title>age {}: {}</title></rect>",
+            x_pos(age as f64, max_x) + bar_width * 0.1,
+            y_pos(count as f64, max_y),
+            bar_width * 0.8,
+            HEIGHT - MARGIN - y_pos(count as f64, max_y),
+            colour,
+            age,
+            count
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Renders the statistics of the section sizes per prefix length as an HTML table
+fn prefix_length_table(network: &Network) -> String {
+    let distribution = network.section_sizes_by_prefix_len();
+    let all = network.section_sizes();
+    let mut html = String::new();
+    html.push_str(
+        "<table>\n<tr><th>Prefix len</th><th>Count</th><th>Average</th><th>Min</th><th>Max</th>\
+         <th>Standard dev</th><th>Median</th><th>P5</th><th>P95</th></tr>\n",
+    );
+    let rows = distribution
+        .iter()
+        .map(|(len, sizes)| (len.to_string(), Stats::new(sizes)))
+        .chain(Some(("All".to_owned(), Stats::new(&all))));
+    for (label, stats) in rows {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{:.2}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+             <td>{}</td><td>{}</td></tr>",
+            label,
+            stats.count(),
+            stats.average(),
+            optional(stats.min().map(|v| v as f64), 0),
+            optional(stats.max().map(|v| v as f64), 0),
+            optional(stats.standard_deviation(), 2),
+            optional(stats.median(), 1),
+            optional(stats.percentile(5.0), 1),
+            optional(stats.percentile(95.0), 1)
+        );
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+/// Renders a self-contained HTML page with the results of the simulation: the structure of the
+/// network over time, the age and drop distributions, the section sizes per prefix length and
+/// the parameters of the run. The charts are inline SVG, so no external tool or resource is
+/// needed to view the page.
+pub fn render(network: &Network) -> String {
+    let output = network.output();
+    let structure = &output.network_structure;
+    let series = |f: fn(&NetworkStructure) -> usize| -> Vec<(f64, f64)> {
+        structure
+            .iter()
+            .enumerate()
+            .map(|(i, data)| ((output.measurement_start + i) as f64, f(data) as f64))
+            .collect()
+    };
+    let size = series(|data| data.size);
+    let sections = series(|data| data.sections);
+    let complete = series(|data| data.complete);
+
+    let mut html = String::new();
+    html.push_str(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Ageing simulation report</title>\n<style>\n\
+         body { font-family: sans-serif; margin: 2em; }\n\
+         table { border-collapse: collapse; margin-bottom: 1em; }\n\
+         th, td { border: 1px solid #999999; padding: 0.2em 0.6em; text-align: right; }\n\
+         </style>\n</head>\n<body>\n<h1>Ageing simulation report</h1>\n",
+    );
+
+    html.push_str("<h2>Network structure</h2>\n");
+    html.push_str(&line_chart(
+        "Network size",
+        &[Series { label: "Network size", colour: "#FF0000", points: size }],
+    ));
+    html.push_str(&line_chart(
+        "Sections",
+        &[
+            Series { label: "Number of sections", colour: "#0000FF", points: sections },
+            Series { label: "Complete groups", colour: "#D0D000", points: complete },
+        ],
+    ));
+
+    html.push_str("<h2>Network state</h2>\n<pre>\n");
+    html.push_str(&escape(&network.to_string()));
+    html.push_str("</pre>\n");
+
+    html.push_str("<h2>Section sizes by prefix length</h2>\n");
+    html.push_str(&prefix_length_table(network));
+
+    html.push_str("<h2>Age distribution</h2>\n");
+    html.push_str(&bar_chart("Age distribution", "#3070B0", &network.age_distribution()));
+
+    html.push_str("<h2>Drops distribution by age</h2>\n");
+    html.push_str(&bar_chart("Drops distribution by age", "#B03030", &output.drops_dist));
+
+    html.push_str("<h2>Parameters</h2>\n<pre>\n");
+    html.push_str(&escape(&format!("{:#?}", network.params())));
+    html.push_str("\n</pre>\n</body>\n</html>\n");
+    html
+}
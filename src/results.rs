@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use network::Network;
+use stats::{Accumulator, Stats};
+
+/// First line of a results file
+const HEADER: &str = "# ageing_sim results";
+
+/// The final metrics of a run, indexed by dotted names like "output.adds",
+/// "section_size.len.5.average" or "age_dist.7".
+/// They are saved as a text file with one "name value" pair per line, so that runs can be
+/// compared afterwards.
+#[derive(Clone, Debug, Default)]
+pub struct Results {
+    metrics: BTreeMap<String, f64>,
+}
+
+impl Results {
+    /// Collects the metrics of a network at the end of a run
+    pub fn from_network(network: &Network) -> Results {
+        let mut results = Results::default();
+        let output = network.output();
+        results.insert("output.adds", output.adds as f64);
+        results.insert("output.drops", output.drops as f64);
+        results.insert("output.rejoins", output.rejoins as f64);
+        results.insert("output.relocations", output.relocations as f64);
+        results.insert("output.rejections", output.rejections as f64);
+        results.insert("output.churn", output.churn as f64);
+        results.insert("output.split_requests", output.split_requests as f64);
+        results.insert("output.splits", output.splits as f64);
+        results.insert("output.merge_requests", output.merge_requests as f64);
+        results.insert("output.merges_superseded", output.merges_superseded as f64);
+        results.insert("output.merges", output.merges as f64);
+        results.insert("output.multi_level_merges", output.multi_level_merges as f64);
+        results.insert("output.elder_changes", output.elder_changes as f64);
+        results.insert("output.elder_set_changes", output.elder_set_changes as f64);
+        results.insert("output.elder_set_changes_per_churn", output.elder_set_changes_per_churn());
+        results.insert("output.growth_rate", output.growth_rate());
+        results.insert("output.rejection_fairness", output.rejection_fairness());
+        results.insert("network.sections", network.num_sections() as f64);
+        results.insert("network.complete", network.complete_sections() as f64);
+        results.insert("network.section_nodes", network.section_sizes().iter().sum::<usize>() as f64);
+        results.insert("network.left_nodes", network.num_left_nodes() as f64);
+        for (len, sizes) in &network.section_sizes_by_prefix_len() {
+            results.insert_stats(&format!("section_size.len.{}", len), &Stats::new(sizes));
+        }
+        results.insert_stats("section_size.all", &Stats::new(&network.section_sizes()));
+        for (age, count) in &network.age_distribution() {
+            results.insert(&format!("age_dist.{}", age), *count as f64);
+        }
+        let classes = &network.params().node_classes;
+        if classes.len() > 1 {
+            let elders = network.class_elders();
+            for (i, dist) in network.class_age_distributions().iter().enumerate() {
+                let name = &classes[i].name;
+                results.insert(&format!("class.{}.nodes", name), dist.values().sum::<usize>() as f64);
+                results.insert(&format!("class.{}.elders", name), elders[i] as f64);
+                for (age, count) in dist {
+                    results.insert(&format!("class.{}.age_dist.{}", name, age), *count as f64);
+                }
+            }
+        }
+        for (age, count) in &output.drops_dist {
+            results.insert(&format!("drops_dist.{}", age), *count as f64);
+        }
+        results.insert_accumulator("per_event.cascade_length", &output.cascade_length);
+        results.insert_accumulator("per_event.relocation_distance", &output.relocation_distance);
+        results.insert_accumulator("per_event.elder_change_interval", &output.elder_change_interval);
+        results.insert_accumulator("per_event.merge_depth", &output.merge_depth);
+        if network.params().retry.is_some() {
+            results.insert("output.retries", output.retries as f64);
+            results.insert("output.give_ups", output.give_ups as f64);
+            results.insert_accumulator("per_event.join_wait", &output.join_wait);
+        }
+        for (i, departure) in output.departures.iter().enumerate() {
+            let name = |field| format!("departure.{}.{}", i, field);
+            results.insert(&name("departed"), departure.departed as f64);
+            results.insert(&name("lost_groups"), departure.lost_groups as f64);
+            results.insert(&name("merge_requests"), departure.merge_requests as f64);
+            results.insert(&name("cascade_length"), departure.cascade_length.unwrap_or(0) as f64);
+            results.insert(&name("merges"), departure.merges as f64);
+            if let Some(recovery_time) = departure.recovery_time {
+                results.insert(&name("recovery_time"), recovery_time as f64);
+            }
+        }
+        results
+    }
+
+    fn insert(&mut self, name: &str, value: f64) {
+        let _ = self.metrics.insert(name.to_owned(), value);
+    }
+
+    fn insert_stats(&mut self, name: &str, stats: &Stats<usize>) {
+        self.insert(&format!("{}.count", name), stats.count() as f64);
+        self.insert(&format!("{}.average", name), stats.average());
+        let optional = [
+            ("min", stats.min().map(|v| v as f64)),
+            ("max", stats.max().map(|v| v as f64)),
+            ("standard_deviation", stats.standard_deviation()),
+            ("median", stats.median()),
+        ];
+        for &(field, value) in &optional {
+            if let Some(value) = value {
+                self.insert(&format!("{}.{}", name, field), value);
+            }
+        }
+    }
+
+    fn insert_accumulator(&mut self, name: &str, accumulator: &Accumulator) {
+        let optional = [
+            ("average", accumulator.average()),
+            ("max", accumulator.max()),
+            ("standard_deviation", accumulator.standard_deviation()),
+            ("median", accumulator.median()),
+            ("p95", accumulator.percentile(95.0)),
+        ];
+        for &(field, value) in &optional {
+            if let Some(value) = value {
+                self.insert(&format!("{}.{}", name, field), value);
+            }
+        }
+    }
+
+    /// Returns the metrics indexed by name
+    pub fn metrics(&self) -> &BTreeMap<String, f64> {
+        &self.metrics
+    }
+
+    /// Saves the results to a file
+    pub fn write(&self, file: &str) {
+        let mut file = File::create(file).unwrap_or_else(|_| panic!("Couldn't create file {}!", file));
+        let _ = writeln!(file, "{}", HEADER);
+        for (name, value) in &self.metrics {
+            let _ = writeln!(file, "{} {}", name, value);
+        }
+    }
+
+    /// Loads results saved by `write`
+    pub fn read(file: &str) -> Result<Results, String> {
+        let mut text = String::new();
+        let _ = File::open(file)
+            .and_then(|mut f| f.read_to_string(&mut text))
+            .map_err(|e| format!("{}: {}", file, e))?;
+        Results::parse(&text).map_err(|e| format!("{}:{}", file, e))
+    }
+
+    /// Parses results in the format written by `write`, starting with its header line
+    fn parse(text: &str) -> Result<Results, String> {
+        if text.lines().next().map(str::trim) != Some(HEADER) {
+            return Err(format!("1: expected the header {:?}", HEADER));
+        }
+        let mut results = Results::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let parsed = match (fields.next(), fields.next(), fields.next()) {
+                (Some(name), Some(value), None) => value.parse().ok().map(|value| (name, value)),
+                _ => None,
+            };
+            match parsed {
+                Some((name, value)) => results.insert(name, value),
+                None => return Err(format!("{}: expected \"name value\", found {:?}", number + 1, line)),
+            }
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let text = format!("{}\n\noutput.adds 12\n# comment\nage_dist.5 3.5\n", HEADER);
+        let results = Results::parse(&text).unwrap();
+        assert_eq!(results.metrics().len(), 2);
+        assert_eq!(results.metrics()["output.adds"], 12.0);
+        assert_eq!(results.metrics()["age_dist.5"], 3.5);
+
+        assert!(Results::parse("output.adds 12\n").unwrap_err().starts_with("1: expected the header"));
+        assert!(Results::parse("").is_err());
+        let malformed = format!("{}\noutput.adds\n", HEADER);
+        assert!(Results::parse(&malformed).unwrap_err().starts_with("2: expected"));
+        let malformed = format!("{}\noutput.adds twelve\n", HEADER);
+        assert!(Results::parse(&malformed).is_err());
+        let malformed = format!("{}\noutput.adds 1 2\n", HEADER);
+        assert!(Results::parse(&malformed).is_err());
+    }
+}
@@ -0,0 +1,331 @@
+use std::fs::File;
+use std::io::Write;
+use network::Network;
+use network::node::Node;
+use network::prefix::{Name, Prefix};
+use network::snapshot;
+use random::random;
+
+/// The quantities an `expect` command can check
+#[derive(Clone, Copy, Debug)]
+enum Quantity {
+    /// number of sections
+    Sections,
+    /// number of complete sections
+    Complete,
+    /// number of nodes in the sections
+    Nodes,
+    /// number of nodes that left the network
+    Left,
+    /// length of the longest prefix
+    PrefixLen,
+}
+
+impl Quantity {
+    fn parse(s: &str) -> Option<Quantity> {
+        match s {
+            "sections" => Some(Quantity::Sections),
+            "complete" => Some(Quantity::Complete),
+            "nodes" => Some(Quantity::Nodes),
+            "left" => Some(Quantity::Left),
+            "prefix-len" => Some(Quantity::PrefixLen),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            Quantity::Sections => "sections",
+            Quantity::Complete => "complete",
+            Quantity::Nodes => "nodes",
+            Quantity::Left => "left",
+            Quantity::PrefixLen => "prefix-len",
+        }
+    }
+
+    fn value(&self, network: &Network) -> usize {
+        match *self {
+            Quantity::Sections => network.num_sections(),
+            Quantity::Complete => network.complete_sections(),
+            Quantity::Nodes => network.section_sizes().iter().sum(),
+            Quantity::Left => network.num_left_nodes(),
+            Quantity::PrefixLen => network.sections().keys().map(|pfx| pfx.len() as usize).max().unwrap_or(0),
+        }
+    }
+}
+
+/// Where a scripted node joins: under a prefix, with a random name, or with a given name
+#[derive(Clone, Copy, Debug)]
+enum Location {
+    Prefix(Prefix),
+    Name(Name),
+}
+
+#[derive(Clone, Debug)]
+enum Command {
+    /// add a node with the given age, or the initial age
+    Add(Location, Option<u8>),
+    /// drop the node with the given name
+    Drop(Name),
+    /// drop the oldest node whose name starts with the prefix
+    DropOldest(Prefix),
+    /// get a node that left to rejoin
+    Rejoin,
+    /// run iterations of random churn
+    Random(usize),
+    /// check quantities of the network
+    Expect(Vec<(Quantity, usize)>),
+    /// print a snapshot of the network, or write it to a file
+    Snapshot(Option<String>),
+}
+
+/// Parses a prefix written as its bits, or "()" for the empty prefix
+fn parse_prefix(s: &str) -> Result<Prefix, String> {
+    if s == "()" {
+        Ok(Prefix::empty())
+    } else {
+        s.parse().map_err(|e| format!("{}", e))
+    }
+}
+
+/// Parses a name written in hexadecimal, with an optional "0x"
+fn parse_name(s: &str) -> Result<Name, String> {
+    s.strip_prefix("0x").unwrap_or(s).parse().map_err(|e| format!("{}", e))
+}
+
+/// Parses the arguments of a command
+fn parse_command(command: &str, args: &[&str]) -> Result<Command, String> {
+    match (command, args) {
+        ("add", &[location]) | ("add", &[location, _]) => {
+            let location = if location.starts_with("0x") {
+                Location::Name(parse_name(location)?)
+            } else {
+                Location::Prefix(parse_prefix(location)?)
+            };
+            let age = match args.get(1) {
+                None => None,
+                Some(arg) => match arg.strip_prefix("age=").map(|age| age.parse()) {
+                    Some(Ok(age)) if age > 0 => Some(age),
+                    Some(_) => return Err("the age must be a number from 1 to 255".to_owned()),
+                    None => return Err(format!("unexpected argument {:?}", arg)),
+                },
+            };
+            Ok(Command::Add(location, age))
+        }
+        ("drop", &[name]) => Ok(Command::Drop(parse_name(name)?)),
+        ("drop-oldest", &[prefix]) => Ok(Command::DropOldest(parse_prefix(prefix)?)),
+        ("rejoin", &[]) => Ok(Command::Rejoin),
+        ("random", &[count]) => count
+            .parse()
+            .map(Command::Random)
+            .map_err(|_| "the number of iterations must be a number".to_owned()),
+        ("expect", _) if !args.is_empty() => {
+            let mut checks = Vec::new();
+            for arg in args {
+                let mut parts = arg.splitn(2, '=');
+                let quantity = parts.next().and_then(Quantity::parse);
+                let value = parts.next().and_then(|value| value.parse().ok());
+                match (quantity, value) {
+                    (Some(quantity), Some(value)) => checks.push((quantity, value)),
+                    _ => {
+                        return Err(format!(
+                            "expected <sections|complete|nodes|left|prefix-len>=<number>, found {:?}",
+                            arg
+                        ))
+                    }
+                }
+            }
+            Ok(Command::Expect(checks))
+        }
+        ("snapshot", &[]) => Ok(Command::Snapshot(None)),
+        ("snapshot", &[file]) => Ok(Command::Snapshot(Some(file.to_owned()))),
+        ("add", _) | ("drop", _) | ("drop-oldest", _) | ("rejoin", _) | ("random", _) | ("expect", _)
+        | ("snapshot", _) => Err(format!("wrong arguments for {}", command)),
+        _ => Err(format!("unknown command {:?}", command)),
+    }
+}
+
+/// A scripted sequence of events, read from a file with one command per line:
+/// - `add <prefix|0xname> [age=N]`: a node joins under the prefix with a random name, or with the
+///   given name, and the initial age unless specified
+/// - `drop <name>`: the node with the given name leaves
+/// - `drop-oldest <prefix>`: the oldest node whose name starts with the prefix leaves
+/// - `rejoin`: a node that left rejoins
+/// - `random N`: N iterations of random churn, following the phase schedule
+/// - `expect <quantity>=K ...`: fails unless the quantities (sections, complete, nodes, left,
+///   prefix-len) have the given values
+/// - `snapshot [FILE]`: prints a snapshot of the network, or writes it to a file
+///
+/// Prefixes are written as bits, or "()" for the empty prefix, and names in hexadecimal. Every
+/// command but `expect` and `snapshot` is run as one iteration, with its churn cascade processed.
+/// Lines starting with '#' are comments.
+pub struct Scenario {
+    /// the commands with their line numbers
+    commands: Vec<(usize, Command)>,
+}
+
+impl Scenario {
+    /// Parses a scenario, reporting the first invalid line
+    pub fn parse(text: &str) -> Result<Scenario, String> {
+        let mut commands = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let number = number + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let command = parse_command(fields[0], &fields[1..]).map_err(|e| format!("line {}: {}", number, e))?;
+            commands.push((number, command));
+        }
+        Ok(Scenario { commands })
+    }
+
+    /// Runs the scenario against the network, starting with iteration `iteration`. Returns the
+    /// number of iterations run, or a description of the first command that failed together with
+    /// the state of the network.
+    pub fn run(&self, network: &mut Network, mut iteration: usize) -> Result<usize, String> {
+        let start = iteration;
+        for &(number, ref command) in &self.commands {
+            let fail = |message: String, network: &Network| {
+                format!(
+                    "line {}: {}\n\nNetwork state:\n{}\n{}",
+                    number,
+                    message,
+                    network,
+                    snapshot::export(network)
+                )
+            };
+            match *command {
+                Command::Add(location, age) => {
+                    let name = match location {
+                        Location::Prefix(prefix) => prefix.substituted_in(random()),
+                        Location::Name(name) => name,
+                    };
+                    let params = network.params();
+                    let node = Node::new(name, age.unwrap_or(params.init_age), params.random_node_class());
+                    network.add_node(node);
+                }
+                Command::Drop(name) => {
+                    if !network.drop_node(name) {
+                        return Err(fail(format!("there is no node {} to drop", name), network));
+                    }
+                }
+                Command::DropOldest(prefix) => {
+                    let oldest = network
+                        .sections()
+                        .iter()
+                        .filter(|&(pfx, _)| pfx.is_compatible_with(&prefix))
+                        .flat_map(|(_, section)| section.nodes())
+                        .filter(|node| prefix.matches(node.name()))
+                        .max_by(|a, b| a.age().cmp(&b.age()).then(b.name().cmp(&a.name())));
+                    match oldest {
+                        Some(node) => {
+                            let _ = network.drop_node(node.name());
+                        }
+                        None => return Err(fail(format!("there is no node under {} to drop", prefix.label()), network)),
+                    }
+                }
+                Command::Rejoin => network.rejoin_random_node(),
+                Command::Random(count) => {
+                    for _ in 0..count {
+                        let (_, probabilities) = network.params().churn_probabilities(iteration);
+                        network.random_event(probabilities);
+                        network.process_events();
+                        network.end_iteration(iteration);
+                        iteration += 1;
+                    }
+                    continue;
+                }
+                Command::Expect(ref checks) => {
+                    let mismatches: Vec<String> = checks
+                        .iter()
+                        .map(|&(quantity, expected)| (quantity, expected, quantity.value(network)))
+                        .filter(|&(_, expected, actual)| expected != actual)
+                        .map(|(quantity, expected, actual)| {
+                            format!("expected {}={}, found {}", quantity.name(), expected, actual)
+                        })
+                        .collect();
+                    if !mismatches.is_empty() {
+                        return Err(fail(mismatches.join(", "), network));
+                    }
+                    continue;
+                }
+                Command::Snapshot(ref file) => {
+                    let snapshot = snapshot::export(network);
+                    match *file {
+                        Some(ref file) => {
+                            let written = File::create(file).and_then(|mut f| f.write_all(snapshot.as_bytes()));
+                            if let Err(e) = written {
+                                return Err(fail(format!("couldn't write snapshot {}: {}", file, e), network));
+                            }
+                        }
+                        None => print!("{}", snapshot),
+                    }
+                    continue;
+                }
+            }
+            network.process_events();
+            network.end_iteration(iteration);
+            iteration += 1;
+        }
+        Ok(iteration - start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefix_and_name_round_trip() {
+        assert_eq!(parse_prefix("()"), Ok(Prefix::empty()));
+        for bits in &["0", "1011"] {
+            let prefix: Prefix = bits.parse().unwrap();
+            assert_eq!(parse_prefix(&prefix.to_string()), Ok(prefix));
+        }
+        assert!(parse_prefix("012").is_err());
+        let name = Name([0xdead_beef, 0, !0, 42]);
+        assert_eq!(parse_name(&name.to_string()), Ok(name));
+        assert_eq!(parse_name(&format!("0x{}", name)), Ok(name));
+        assert!(parse_name("0x").is_err());
+    }
+
+    #[test]
+    fn commands() {
+        match parse_command("add", &["01", "age=7"]) {
+            Ok(Command::Add(Location::Prefix(prefix), Some(7))) => assert_eq!(prefix.to_string(), "01"),
+            other => panic!("{:?}", other),
+        }
+        match parse_command("add", &["0x80"]) {
+            Ok(Command::Add(Location::Name(name), None)) => assert_eq!(name, parse_name("80").unwrap()),
+            other => panic!("{:?}", other),
+        }
+        assert!(matches!(parse_command("drop-oldest", &["()"]), Ok(Command::DropOldest(_))));
+        assert!(matches!(parse_command("random", &["100"]), Ok(Command::Random(100))));
+        assert!(matches!(parse_command("snapshot", &[]), Ok(Command::Snapshot(None))));
+        match parse_command("expect", &["nodes=3", "prefix-len=2"]) {
+            Ok(Command::Expect(checks)) => {
+                let checks: Vec<_> = checks.iter().map(|&(quantity, value)| (quantity.name(), value)).collect();
+                assert_eq!(checks, vec![("nodes", 3), ("prefix-len", 2)]);
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(parse_command("add", &["01", "age=0"]).is_err());
+        assert!(parse_command("expect", &[]).is_err());
+        assert!(parse_command("expect", &["size=3"]).is_err());
+        assert!(parse_command("rejoin", &["now"]).is_err());
+        assert!(parse_command("split", &[]).is_err());
+    }
+
+    #[test]
+    fn scenario() {
+        let scenario = Scenario::parse("# comment\n\nadd ()\n  random 10\nexpect sections=1\n").unwrap();
+        let numbers: Vec<usize> = scenario.commands.iter().map(|&(number, _)| number).collect();
+        assert_eq!(numbers, vec![3, 4, 5]);
+        assert_eq!(
+            Scenario::parse("add ()\nrandom ten\n").err(),
+            Some("line 2: the number of iterations must be a number".to_owned())
+        );
+    }
+}
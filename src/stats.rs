@@ -1,73 +1,677 @@
-use std::{fmt, usize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Write;
+use std::f64;
 
-// Compute count, average, min, max and possibly standard deviation of a vec of usize values
-pub struct Stats {
+/// Number of buckets of the histogram included in the JSON rendering
+const JSON_HISTOGRAM_BUCKETS: usize = 10;
+
+/// The percentiles reported besides the median
+const PERCENTILES: [f64; 5] = [5.0, 25.0, 75.0, 95.0, 99.0];
+
+/// A numeric type whose values can be summarised by `Stats`
+pub trait Sample: Copy + PartialOrd {
+    /// Converts the value for the computations of averages and percentiles
+    fn to_f64(self) -> f64;
+    /// Whether the type only has integral values, which decides how values and histogram bucket
+    /// bounds are printed
+    fn is_integral() -> bool;
+}
+
+macro_rules! integral_sample {
+    ($($t:ty),*) => {
+        $(
+            impl Sample for $t {
+                fn to_f64(self) -> f64 {
+                    self as f64
+                }
+                fn is_integral() -> bool {
+                    true
+                }
+            }
+        )*
+    };
+}
+
+integral_sample!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+impl Sample for f32 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+    fn is_integral() -> bool {
+        false
+    }
+}
+
+impl Sample for f64 {
+    fn to_f64(self) -> f64 {
+        self
+    }
+    fn is_integral() -> bool {
+        false
+    }
+}
+
+/// Formats a value of a sample type: integral types without decimals, the others with the given
+/// precision
+fn format_value<T: Sample>(value: T, precision: usize) -> String {
+    if T::is_integral() {
+        format!("{}", value.to_f64())
+    } else {
+        format!("{:.*}", precision, value.to_f64())
+    }
+}
+
+/// Formats an optional value for the markdown rendering
+fn format_option(value: Option<f64>, precision: usize) -> String {
+    value.map_or("None".to_owned(), |value| format!("{:.*}", precision, value))
+}
+
+/// Formats an optional value for the markdown rendering, without decimals if it is a whole number
+fn format_number(value: Option<f64>, precision: usize) -> String {
+    match value {
+        Some(value) if value == value.trunc() => format!("{}", value),
+        value => format_option(value, precision),
+    }
+}
+
+/// Formats an optional value for the CSV rendering, where a missing value is an empty field
+fn csv_field(value: Option<f64>, precision: usize) -> String {
+    value.map_or(String::new(), |value| format!("{:.*}", precision, value))
+}
+
+/// Formats an optional value for the JSON rendering, where NaN and infinities are not allowed
+fn json_number(value: Option<f64>) -> String {
+    match value {
+        Some(value) if value.is_finite() => format!("{}", value),
+        _ => "null".to_owned(),
+    }
+}
+
+/// A bucket of a histogram: the number of values in the half-open range [lower, upper), the last
+/// bucket also including its upper bound
+#[derive(Clone, Copy, Debug)]
+pub struct Bucket {
+    pub lower: f64,
+    pub upper: f64,
+    pub count: usize,
+}
+
+/// A histogram of the values summarised by `Stats`
+#[derive(Clone, Debug)]
+pub struct Histogram {
+    integral: bool,
+    buckets: Vec<Bucket>,
+}
+
+impl Histogram {
+    pub fn buckets(&self) -> &[Bucket] {
+        &self.buckets
+    }
+}
+
+// Display histogram as a markdown table
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let precision = f.precision().unwrap_or(2usize);
+        writeln!(f, "|        Range        |  Count  |")?;
+        writeln!(f, "|--------------------:|--------:|")?;
+        for bucket in &self.buckets {
+            let range = if self.integral {
+                // Integral buckets are written with inclusive bounds
+                format!("{} - {}", bucket.lower, bucket.upper - 1.0)
+            } else {
+                format!("{:.*} - {:.*}", precision, bucket.lower, precision, bucket.upper)
+            };
+            writeln!(f, "| {:>19} | {:>7} |", range, bucket.count)?;
+        }
+        Ok(())
+    }
+}
+
+// Compute count, average, min, max, possibly standard deviation, median and percentiles of a
+// vec of values. The sorted values are kept to compute histograms on demand.
+pub struct Stats<T> {
     count: usize,
     average: f64,
-    min: usize,
-    max: usize,
+    min: Option<T>,
+    max: Option<T>,
     standard_deviation: Option<f64>,
+    sorted: Vec<T>,
 }
-impl Stats {
-    pub fn new(values: &Vec<usize>) -> Self {
-        let mut sum = 0usize;
-        let mut max = 0usize;
-        let mut min = usize::MAX;
-        for val in values {
-            sum += *val;
-            if max < *val {
-                max = *val;
-            }
-            if min > *val {
-                min = *val;
-            }
-        }
+
+impl<T: Sample> Stats<T> {
+    pub fn new(values: &[T]) -> Self {
+        let mut sorted = values.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).expect("Values must be comparable!"));
+        let sum: f64 = values.iter().map(|val| val.to_f64()).sum();
         let count = values.len() as f64;
-        let average = sum as f64 / count;
-        let standard_deviation = if count == 1f64 {
+        let average = sum / count;
+        let standard_deviation = if values.len() <= 1 {
             // More than one samples are needed to apply the sample standard deviation formula
             None
         } else {
             let mut variance = 0f64;
             for val in values {
-                variance += (*val as f64 - average).powi(2);
+                variance += (val.to_f64() - average).powi(2);
             }
-            variance = variance / (count - 1f64);
+            variance /= count - 1f64;
             let standard_deviation = variance.sqrt();
             Some(standard_deviation)
         };
         Stats {
             count: values.len(),
-            average: average,
-            min: min,
-            max: max,
-            standard_deviation: standard_deviation,
+            average,
+            min: sorted.first().cloned(),
+            max: sorted.last().cloned(),
+            standard_deviation,
+            sorted,
+        }
+    }
+    pub fn count(&self) -> usize {
+        self.count
+    }
+    pub fn average(&self) -> f64 {
+        self.average
+    }
+    pub fn min(&self) -> Option<T> {
+        self.min
+    }
+    pub fn max(&self) -> Option<T> {
+        self.max
+    }
+    pub fn standard_deviation(&self) -> Option<f64> {
+        self.standard_deviation
+    }
+    /// Returns the p-th percentile (0 <= p <= 100), interpolating linearly between the closest
+    /// ranks. None if there are no values.
+    pub fn percentile(&self, p: f64) -> Option<f64> {
+        if self.sorted.is_empty() {
+            return None;
+        }
+        let rank = p.clamp(0.0, 100.0) / 100.0 * (self.sorted.len() - 1) as f64;
+        let lower = rank.floor() as usize;
+        let upper = rank.ceil() as usize;
+        let (low, high) = (self.sorted[lower].to_f64(), self.sorted[upper].to_f64());
+        Some(low + (high - low) * (rank - lower as f64))
+    }
+    pub fn median(&self) -> Option<f64> {
+        self.percentile(50.0)
+    }
+    /// Splits the range of the values into `buckets` buckets of equal width and counts the
+    /// values in each of them. For integral types the width is rounded up to a whole number, so
+    /// there may be fewer buckets than requested.
+    pub fn histogram(&self, buckets: usize) -> Histogram {
+        let integral = T::is_integral();
+        let (min, max) = match (self.min, self.max) {
+            (Some(min), Some(max)) => (min.to_f64(), max.to_f64()),
+            _ => return Histogram { integral, buckets: vec![] },
+        };
+        let buckets = buckets.max(1);
+        let width = if integral {
+            ((max - min + 1.0) / buckets as f64).ceil()
+        } else if max > min {
+            (max - min) / buckets as f64
+        } else {
+            1.0
+        };
+        let num_buckets = if integral {
+            ((max - min + 1.0) / width).ceil() as usize
+        } else {
+            buckets
+        };
+        let mut result: Vec<Bucket> = (0..num_buckets)
+            .map(|i| Bucket {
+                lower: min + i as f64 * width,
+                upper: min + (i + 1) as f64 * width,
+                count: 0,
+            })
+            .collect();
+        for val in &self.sorted {
+            let index = ((val.to_f64() - min) / width) as usize;
+            result[index.min(num_buckets - 1)].count += 1;
         }
+        Histogram { integral, buckets: result }
     }
     pub fn get_header_line() -> &'static str {
-        return &"|  Count  | Average | Min | Max | Standard dev |";
+        "|  Count  | Average | Min | Max | Standard dev | Median |   P5   |  P25   |  P75   |  P95   |  P99   |"
     }
     pub fn get_separator_line() -> &'static str {
-        return &"|--------:|--------:|----:|----:|-------------:|";
+        "|--------:|--------:|----:|----:|-------------:|-------:|-------:|-------:|-------:|-------:|-------:|"
+    }
+    pub fn get_csv_header() -> &'static str {
+        "count,average,min,max,standard_deviation,median,p5,p25,p75,p95,p99"
+    }
+    /// Renders the stats as a CSV line matching `get_csv_header`
+    pub fn to_csv(&self) -> String {
+        let value_precision = if T::is_integral() { 0 } else { 6 };
+        let mut fields = vec![
+            self.count.to_string(),
+            format!("{}", self.average),
+            csv_field(self.min.map(Sample::to_f64), value_precision),
+            csv_field(self.max.map(Sample::to_f64), value_precision),
+            csv_field(self.standard_deviation, 6),
+            csv_field(self.median(), 6),
+        ];
+        fields.extend(PERCENTILES.iter().map(|&p| csv_field(self.percentile(p), 6)));
+        fields.join(",")
+    }
+    /// Renders the stats as a JSON object, including a histogram
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        let _ = write!(
+            json,
+            "{{\"count\":{},\"average\":{},\"min\":{},\"max\":{},\"standard_deviation\":{},\
+             \"median\":{}",
+            self.count,
+            json_number(Some(self.average)),
+            json_number(self.min.map(Sample::to_f64)),
+            json_number(self.max.map(Sample::to_f64)),
+            json_number(self.standard_deviation),
+            json_number(self.median())
+        );
+        for &p in &PERCENTILES {
+            let _ = write!(json, ",\"p{}\":{}", p, json_number(self.percentile(p)));
+        }
+        let buckets: Vec<String> = self.histogram(JSON_HISTOGRAM_BUCKETS)
+            .buckets()
+            .iter()
+            .map(|b| format!("{{\"lower\":{},\"upper\":{},\"count\":{}}}", b.lower, b.upper, b.count))
+            .collect();
+        let _ = write!(json, ",\"histogram\":[{}]}}", buckets.join(","));
+        json
     }
 }
 
 // Display stats as a markdown table
-impl fmt::Display for Stats {
+impl<T: Sample> fmt::Display for Stats<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let precision = f.precision().unwrap_or(2usize);
-        try!(write!(
+        let value = |value: Option<T>| value.map_or("None".to_owned(), |v| format_value(v, precision));
+        write!(
             f,
             "{:>7} | {:>7.*} | {:>3} | {:>3} | ",
             self.count,
             precision,
             self.average,
-            self.min,
-            self.max
-        ));
+            value(self.min),
+            value(self.max)
+        )?;
         match self.standard_deviation {
-            None => write!(f, "        None |"),
-            Some(standard_deviation) => write!(f, "{:>12.*} |", precision, standard_deviation),
+            None => write!(f, "        None |")?,
+            Some(standard_deviation) => write!(f, "{:>12.*} |", precision, standard_deviation)?,
+        }
+        write!(f, " {:>6} |", format_option(self.median(), precision))?;
+        for &p in &PERCENTILES {
+            write!(f, " {:>6} |", format_option(self.percentile(p), precision))?;
+        }
+        Ok(())
+    }
+}
+
+/// Relative accuracy of the quantiles estimated by `Accumulator`
+const QUANTILE_ACCURACY: f64 = 0.01;
+
+/// Online accumulator of statistics over a stream of values, for metrics that are sampled too
+/// often to keep every value like `Stats` does.
+/// Mean and variance are computed with Welford's method. Quantiles are estimated from a sparse
+/// histogram with logarithmic buckets (as in DDSketch), so that any estimated quantile is within
+/// `QUANTILE_ACCURACY` of a value of the right rank. Both are exactly mergeable, which allows
+/// combining the accumulators of several runs.
+#[derive(Clone, Debug)]
+pub struct Accumulator {
+    count: u64,
+    mean: f64,
+    // sum of the squared differences from the current mean
+    m2: f64,
+    min: f64,
+    max: f64,
+    // log(gamma) where gamma = (1 + accuracy) / (1 - accuracy) is the ratio between the bounds
+    // of a bucket
+    log_gamma: f64,
+    // bucket counts of the positive values and of the magnitudes of the negative values
+    positive: BTreeMap<i32, u64>,
+    negative: BTreeMap<i32, u64>,
+    zeros: u64,
+    // whether all the values are whole numbers, in which case the estimates are rounded
+    integral: bool,
+}
+
+impl Default for Accumulator {
+    fn default() -> Self {
+        Accumulator::new()
+    }
+}
+
+impl Accumulator {
+    pub fn new() -> Self {
+        let gamma = (1.0 + QUANTILE_ACCURACY) / (1.0 - QUANTILE_ACCURACY);
+        Accumulator {
+            count: 0,
+            mean: 0.0,
+            m2: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            log_gamma: gamma.ln(),
+            positive: BTreeMap::new(),
+            negative: BTreeMap::new(),
+            zeros: 0,
+            integral: true,
+        }
+    }
+
+    /// Returns the index of the bucket of a strictly positive magnitude
+    fn bucket(&self, magnitude: f64) -> i32 {
+        (magnitude.ln() / self.log_gamma).ceil() as i32
+    }
+
+    /// Returns the estimated value of the magnitudes in a bucket
+    fn bucket_value(&self, index: i32) -> f64 {
+        let gamma = self.log_gamma.exp();
+        2.0 * (self.log_gamma * index as f64).exp() / (gamma + 1.0)
+    }
+
+    /// Adds a value to the accumulator
+    pub fn add<T: Sample>(&mut self, value: T) {
+        let value = value.to_f64();
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.integral &= value == value.trunc();
+        if value > 0.0 {
+            let index = self.bucket(value);
+            *self.positive.entry(index).or_insert(0) += 1;
+        } else if value < 0.0 {
+            let index = self.bucket(-value);
+            *self.negative.entry(index).or_insert(0) += 1;
+        } else {
+            self.zeros += 1;
+        }
+    }
+
+    /// Adds the values accumulated by another accumulator, as if they had been added to this one
+    #[allow(unused)]
+    pub fn merge(&mut self, other: &Accumulator) {
+        if other.count == 0 {
+            return;
+        }
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+        self.mean += delta * other.count as f64 / count as f64;
+        self.m2 += other.m2 + delta * delta * self.count as f64 * other.count as f64 / count as f64;
+        self.count = count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        for (index, n) in &other.positive {
+            *self.positive.entry(*index).or_insert(0) += n;
+        }
+        for (index, n) in &other.negative {
+            *self.negative.entry(*index).or_insert(0) += n;
+        }
+        self.zeros += other.zeros;
+        self.integral &= other.integral;
+    }
+
+    pub fn average(&self) -> Option<f64> {
+        if self.count > 0 { Some(self.mean) } else { None }
+    }
+    pub fn min(&self) -> Option<f64> {
+        if self.count > 0 { Some(self.min) } else { None }
+    }
+    pub fn max(&self) -> Option<f64> {
+        if self.count > 0 { Some(self.max) } else { None }
+    }
+    pub fn standard_deviation(&self) -> Option<f64> {
+        if self.count > 1 {
+            Some((self.m2 / (self.count - 1) as f64).sqrt())
+        } else {
+            None
+        }
+    }
+
+    /// Returns an estimate of the p-th percentile (0 <= p <= 100). None if there are no values.
+    pub fn percentile(&self, p: f64) -> Option<f64> {
+        if self.integral {
+            self.estimate_percentile(p).map(f64::round)
+        } else {
+            self.estimate_percentile(p)
+        }
+    }
+
+    fn estimate_percentile(&self, p: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = (p.clamp(0.0, 100.0) / 100.0 * (self.count - 1) as f64).round() as u64;
+        let mut seen = 0;
+        // The negative values in increasing order are the magnitudes in decreasing order
+        for (index, n) in self.negative.iter().rev() {
+            seen += n;
+            if seen > rank {
+                return Some(-self.bucket_value(*index).min(-self.min));
+            }
+        }
+        seen += self.zeros;
+        if seen > rank {
+            return Some(0.0);
+        }
+        for (index, n) in &self.positive {
+            seen += n;
+            if seen > rank {
+                return Some(self.bucket_value(*index).max(self.min).min(self.max));
+            }
+        }
+        Some(self.max)
+    }
+
+    pub fn median(&self) -> Option<f64> {
+        self.percentile(50.0)
+    }
+}
+
+// Display accumulated stats as a markdown table row, with the same columns as `Stats`
+impl fmt::Display for Accumulator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let precision = f.precision().unwrap_or(2usize);
+        write!(
+            f,
+            "{:>7} | {:>7} | {:>3} | {:>3} | {:>12} |",
+            self.count,
+            format_option(self.average(), precision),
+            format_number(self.min(), precision),
+            format_number(self.max(), precision),
+            format_option(self.standard_deviation(), precision)
+        )?;
+        write!(f, " {:>6} |", format_option(self.median(), precision))?;
+        for &p in &PERCENTILES {
+            write!(f, " {:>6} |", format_option(self.percentile(p), precision))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles() {
+        let stats = Stats::new(&[4u32, 1, 3, 2]);
+        assert_eq!(stats.median(), Some(2.5));
+        assert_eq!(stats.percentile(0.0), Some(1.0));
+        assert_eq!(stats.percentile(25.0), Some(1.75));
+        assert_eq!(stats.percentile(100.0), Some(4.0));
+        // out of range percentiles are clamped
+        assert_eq!(stats.percentile(-10.0), Some(1.0));
+        assert_eq!(stats.percentile(150.0), Some(4.0));
+
+        let single = Stats::new(&[7u32]);
+        assert_eq!(single.median(), Some(7.0));
+        assert_eq!(single.percentile(99.0), Some(7.0));
+        assert_eq!(single.standard_deviation(), None);
+
+        let empty = Stats::<u32>::new(&[]);
+        assert_eq!(empty.median(), None);
+        assert_eq!(empty.percentile(5.0), None);
+        assert_eq!(empty.min(), None);
+    }
+
+    #[test]
+    fn histogram() {
+        let counts = |histogram: Histogram| -> Vec<usize> {
+            histogram.buckets().iter().map(|bucket| bucket.count).collect()
+        };
+        assert!(Stats::<u32>::new(&[]).histogram(10).buckets().is_empty());
+
+        // integral widths are rounded up: 10 values in buckets of 4
+        let stats = Stats::new(&(0u32..10).collect::<Vec<_>>());
+        let histogram = stats.histogram(3);
+        assert_eq!(histogram.buckets()[1].lower, 4.0);
+        assert_eq!(histogram.buckets()[1].upper, 8.0);
+        assert_eq!(counts(histogram), vec![4, 4, 2]);
+
+        // the maximum falls in the last bucket
+        let stats = Stats::new(&[0.0, 0.5, 1.0, 2.0]);
+        assert_eq!(counts(stats.histogram(2)), vec![2, 2]);
+
+        // a single value, or all values equal, give a range of width 0
+        assert_eq!(counts(Stats::new(&[5u32]).histogram(10)), vec![1]);
+        assert_eq!(counts(Stats::new(&[5u32, 5, 5]).histogram(10)), vec![3]);
+        let equal = Stats::new(&[2.5, 2.5]).histogram(4);
+        assert_eq!(counts(equal.clone()), vec![2, 0, 0, 0]);
+        assert_eq!(equal.buckets()[0].lower, 2.5);
+    }
+
+    #[test]
+    fn csv_and_json() {
+        let fields = Stats::<u32>::get_csv_header().split(',').count();
+        let stats = Stats::new(&[4u32, 1, 3, 2]);
+        assert_eq!(
+            stats.to_csv(),
+            "4,2.5,1,4,1.290994,2.500000,1.150000,1.750000,3.250000,3.850000,3.970000"
+        );
+        assert_eq!(stats.to_csv().split(',').count(), fields);
+        assert_eq!(Stats::<u32>::new(&[]).to_csv().split(',').count(), fields);
+
+        assert_eq!(
+            Stats::new(&[2u32, 2, 2]).to_json(),
+            "{\"count\":3,\"average\":2,\"min\":2,\"max\":2,\"standard_deviation\":0,\
+             \"median\":2,\"p5\":2,\"p25\":2,\"p75\":2,\"p95\":2,\"p99\":2,\
+             \"histogram\":[{\"lower\":2,\"upper\":3,\"count\":3}]}"
+        );
+        // the average of no values is NaN, which isn't valid JSON
+        assert_eq!(
+            Stats::<u32>::new(&[]).to_json(),
+            "{\"count\":0,\"average\":null,\"min\":null,\"max\":null,\
+             \"standard_deviation\":null,\"median\":null,\"p5\":null,\"p25\":null,\
+             \"p75\":null,\"p95\":null,\"p99\":null,\"histogram\":[]}"
+        );
+    }
+
+    /// Deterministic values of both signs, with some zeros and a wide range of magnitudes
+    fn values(count: usize, offset: usize) -> Vec<f64> {
+        (offset..offset + count)
+            .map(|i| match i % 7 {
+                0 => 0.0,
+                1 => -(((i * 31) % 97) as f64) / 3.0 - 0.5,
+                _ => ((i * 7919) % 1009) as f64 * 1.7 + 0.25,
+            })
+            .collect()
+    }
+
+    fn accumulate(values: &[f64]) -> Accumulator {
+        let mut accumulator = Accumulator::new();
+        for &value in values {
+            accumulator.add(value);
         }
+        accumulator
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() <= 1e-9 * expected.abs().max(1.0),
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn accumulator_mean_and_variance() {
+        let values = values(1000, 0);
+        let accumulator = accumulate(&values);
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let variance = values.iter().map(|value| (value - mean).powi(2)).sum::<f64>()
+            / (values.len() - 1) as f64;
+        assert_close(accumulator.average().unwrap(), mean);
+        assert_close(accumulator.standard_deviation().unwrap(), variance.sqrt());
+        assert_eq!(accumulator.min(), values.iter().cloned().reduce(f64::min));
+        assert_eq!(accumulator.max(), values.iter().cloned().reduce(f64::max));
+
+        let empty = Accumulator::new();
+        assert_eq!(empty.average(), None);
+        assert_eq!(empty.median(), None);
+        assert_eq!(accumulate(&[3.0]).standard_deviation(), None);
+    }
+
+    #[test]
+    fn accumulator_quantile_accuracy() {
+        let mut values = values(1000, 0);
+        let accumulator = accumulate(&values);
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        for p in (0..101).map(|p| p as f64) {
+            let rank = (p / 100.0 * (values.len() - 1) as f64).round() as usize;
+            let exact = values[rank];
+            let estimate = accumulator.percentile(p).unwrap();
+            assert!(
+                (estimate - exact).abs() <= QUANTILE_ACCURACY * exact.abs() + 1e-12,
+                "P{}: estimated {} for {}",
+                p,
+                estimate,
+                exact
+            );
+        }
+        // whole numbers give whole estimates
+        let integral = accumulate(&[1.0, 2.0, 3.0, 1000.0, 1001.0]);
+        assert_eq!(integral.median(), Some(3.0));
+        assert_eq!(integral.percentile(100.0), Some(1001.0));
+    }
+
+    #[test]
+    fn accumulator_merge() {
+        let (first, second) = (values(300, 0), values(700, 300));
+        let mut merged = accumulate(&first);
+        merged.merge(&accumulate(&second));
+        let all: Vec<f64> = first.iter().chain(&second).cloned().collect();
+        let single = accumulate(&all);
+        assert_eq!(merged.count, single.count);
+        assert_eq!(merged.min(), single.min());
+        assert_eq!(merged.max(), single.max());
+        assert_close(merged.average().unwrap(), single.average().unwrap());
+        assert_close(
+            merged.standard_deviation().unwrap(),
+            single.standard_deviation().unwrap(),
+        );
+        assert_eq!(merged.positive, single.positive);
+        assert_eq!(merged.negative, single.negative);
+        assert_eq!(merged.zeros, single.zeros);
+        assert_eq!(merged.integral, single.integral);
+        for &p in &PERCENTILES {
+            assert_eq!(merged.percentile(p), single.percentile(p));
+        }
+
+        // merging into or from an empty accumulator
+        let mut empty = Accumulator::new();
+        empty.merge(&single);
+        assert_eq!(empty.count, single.count);
+        assert_close(empty.average().unwrap(), single.average().unwrap());
+        let mut copy = single.clone();
+        copy.merge(&Accumulator::new());
+        assert_eq!(copy.count, single.count);
+        assert_eq!(copy.average(), single.average());
     }
 }
@@ -0,0 +1,108 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+use network::Network;
+use params::StopCondition;
+
+/// Number of samples taken over the window of a steady state condition
+const STEADY_STATE_SAMPLES: usize = 100;
+
+/// Returns the mean age of the nodes in the sections
+fn mean_age(network: &Network) -> f64 {
+    let (sum, count) = network
+        .age_distribution()
+        .iter()
+        .fold((0, 0), |(sum, count), (&age, &n)| (sum + age as usize * n, count + n));
+    sum as f64 / count.max(1) as f64
+}
+
+/// Returns whether all the values are within `tolerance`, relatively to their mean
+fn is_steady<I: Iterator<Item = f64>>(values: I, tolerance: f64) -> bool {
+    let (min, max, sum, count) = values.fold(
+        (f64::MAX, f64::MIN, 0.0, 0),
+        |(min, max, sum, count), v| (min.min(v), max.max(v), sum + v, count + 1),
+    );
+    let mean = sum / count as f64;
+    max - min <= tolerance * mean.abs()
+}
+
+/// Samples of the mean age and the number of sections over the window of a steady state
+/// condition
+struct Window {
+    step: usize,
+    samples: VecDeque<(f64, f64)>,
+}
+
+/// Evaluates the stop conditions after each iteration
+pub struct StopChecker {
+    conditions: Vec<StopCondition>,
+    start: Instant,
+    windows: Vec<Option<Window>>,
+}
+
+impl StopChecker {
+    pub fn new(conditions: &[StopCondition]) -> StopChecker {
+        let windows = conditions
+            .iter()
+            .map(|condition| match *condition {
+                StopCondition::SteadyState { window, .. } => Some(Window {
+                    step: (window / STEADY_STATE_SAMPLES).max(1),
+                    samples: VecDeque::new(),
+                }),
+                _ => None,
+            })
+            .collect();
+        StopChecker {
+            conditions: conditions.to_vec(),
+            start: Instant::now(),
+            windows,
+        }
+    }
+
+    /// Returns a description of the first condition met at the end of iteration `iteration`,
+    /// if any
+    pub fn check(&mut self, iteration: usize, network: &Network) -> Option<String> {
+        for (condition, window) in self.conditions.iter().zip(self.windows.iter_mut()) {
+            let fired = match *condition {
+                StopCondition::Nodes(count) => network.section_sizes().iter().sum::<usize>() >= count,
+                StopCondition::Sections(count) => network.num_sections() >= count,
+                StopCondition::PrefixLen(len) => network.sections().keys().any(|pfx| pfx.len() >= len),
+                StopCondition::Time(seconds) => {
+                    let elapsed = self.start.elapsed();
+                    elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 * 1e-9 >= seconds
+                }
+                StopCondition::SteadyState { window: size, tolerance } => {
+                    let window = window.as_mut().unwrap();
+                    if iteration % window.step != 0 {
+                        continue;
+                    }
+                    window.samples.push_back((mean_age(network), network.num_sections() as f64));
+                    if window.samples.len() > size / window.step {
+                        let _ = window.samples.pop_front();
+                    }
+                    window.samples.len() == size / window.step
+                        && is_steady(window.samples.iter().map(|s| s.0), tolerance)
+                        && is_steady(window.samples.iter().map(|s| s.1), tolerance)
+                }
+            };
+            if fired {
+                return Some(describe(condition));
+            }
+        }
+        None
+    }
+}
+
+/// Returns the description of a condition reported when it fires
+fn describe(condition: &StopCondition) -> String {
+    match *condition {
+        StopCondition::Nodes(count) => format!("{} nodes reached", count),
+        StopCondition::Sections(count) => format!("{} sections reached", count),
+        StopCondition::PrefixLen(len) => format!("prefix length {} reached", len),
+        StopCondition::Time(seconds) => format!("time budget of {} seconds used", seconds),
+        StopCondition::SteadyState { window, tolerance } => format!(
+            "steady state, mean age and section count within {}% over {} iterations",
+            tolerance * 100.0,
+            window
+        ),
+    }
+}